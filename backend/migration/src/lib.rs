@@ -7,6 +7,21 @@ mod m20241006_000003_lone_role_info;
 mod m20241006_000005_room_identity_info;
 mod m20241006_000006_assoc_lone_user;
 mod m20241006_000007_assoc_room_user;
+mod m20250215_000008_user_password_hash;
+mod m20250215_000009_refresh_token;
+mod m20250215_000010_token_revocation;
+mod m20250215_000011_user_session;
+mod m20250215_000012_two_factor;
+mod m20250215_000013_lone_owner_cap;
+mod m20250215_000014_lone_invite;
+mod m20250215_000015_lone_ban;
+mod m20250215_000016_room_identity_deny;
+mod m20250215_000017_role_position;
+mod m20250215_000018_room_order;
+mod m20250215_000019_room_category;
+mod m20250215_000020_message;
+mod m20250215_000021_message_revision;
+mod m20250215_000022_message_reaction;
 
 
 pub struct Migrator;
@@ -22,6 +37,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20241006_000005_room_identity_info::Migration),
             Box::new(m20241006_000006_assoc_lone_user::Migration),
             Box::new(m20241006_000007_assoc_room_user::Migration),
+            Box::new(m20250215_000008_user_password_hash::Migration),
+            Box::new(m20250215_000009_refresh_token::Migration),
+            Box::new(m20250215_000010_token_revocation::Migration),
+            Box::new(m20250215_000011_user_session::Migration),
+            Box::new(m20250215_000012_two_factor::Migration),
+            Box::new(m20250215_000013_lone_owner_cap::Migration),
+            Box::new(m20250215_000014_lone_invite::Migration),
+            Box::new(m20250215_000015_lone_ban::Migration),
+            Box::new(m20250215_000016_room_identity_deny::Migration),
+            Box::new(m20250215_000017_role_position::Migration),
+            Box::new(m20250215_000018_room_order::Migration),
+            Box::new(m20250215_000019_room_category::Migration),
+            Box::new(m20250215_000020_message::Migration),
+            Box::new(m20250215_000021_message_revision::Migration),
+            Box::new(m20250215_000022_message_reaction::Migration),
         ]
     }
 }
@@ -0,0 +1,29 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Argon2id PHC strings don't fit into the old 64-char column.
+        manager.alter_table(
+            Table::alter()
+                .table(UserInfo::Table)
+                .modify_column(string_len(UserInfo::Password, 128))
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(UserInfo::Table)
+                .modify_column(string_len(UserInfo::Password, 64))
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+}
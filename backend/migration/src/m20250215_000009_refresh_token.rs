@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(RefreshToken::Table)
+                .if_not_exists()
+                .col(pk_auto(RefreshToken::Id))
+                .col(integer(RefreshToken::UserId))
+                .col(string_len(RefreshToken::FamilyId, 32))
+                .col(string_len_uniq(RefreshToken::TokenHash, 64))
+                .col(boolean(RefreshToken::Used).default(false))
+                .col(boolean(RefreshToken::Revoked).default(false))
+                .col(timestamp(RefreshToken::ExpireAt))
+
+                .col(timestamp(RefreshToken::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(RefreshToken::Table, RefreshToken::UserId)
+                .to(  UserInfo::Table,     UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_refresh_token_family_id")
+                .table(RefreshToken::Table)
+                .col(RefreshToken::FamilyId)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_foreign_key(ForeignKey::drop().table(RefreshToken::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(RefreshToken::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum RefreshToken {
+    Table,
+    Id,
+    UserId,
+    FamilyId,
+    TokenHash,
+    Used,
+    Revoked,
+    ExpireAt,
+    CreatedAt,
+}
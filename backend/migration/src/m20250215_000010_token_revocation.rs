@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(TokenRevocation::Table)
+                .if_not_exists()
+                .col(pk_auto(TokenRevocation::Id))
+                .col(integer(TokenRevocation::UserId))
+                // NULL revokes every token of the user issued before `revoked_at`.
+                .col(string_len_null(TokenRevocation::Jti, 32).unique_key())
+                .col(timestamp(TokenRevocation::RevokedAt).default(Expr::current_timestamp()))
+                .col(timestamp(TokenRevocation::ExpireAt))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(TokenRevocation::Table, TokenRevocation::UserId)
+                .to(  UserInfo::Table,        UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_foreign_key(ForeignKey::drop().table(TokenRevocation::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(TokenRevocation::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TokenRevocation {
+    Table,
+    Id,
+    UserId,
+    Jti,
+    SessionId,
+    RevokedAt,
+    ExpireAt,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20250215_000010_token_revocation::TokenRevocation;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(UserSession::Table)
+                .if_not_exists()
+                .col(string_len(UserSession::Id, 32).primary_key())
+                .col(integer(UserSession::UserId))
+                .col(string_len(UserSession::Ip, 64))
+                .col(string_len_null(UserSession::UserAgent, 256))
+                .col(boolean(UserSession::Revoked).default(false))
+
+                .col(timestamp(UserSession::CreatedAt).default(Expr::current_timestamp()))
+                .col(timestamp(UserSession::LastSeen).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(UserSession::Table, UserSession::UserId)
+                .to(  UserInfo::Table,    UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // Revoking a session revokes every access token minted for it.
+        manager.alter_table(
+            Table::alter()
+                .table(TokenRevocation::Table)
+                .add_column(string_len_null(TokenRevocation::SessionId, 32))
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(TokenRevocation::Table)
+                .drop_column(TokenRevocation::SessionId)
+                .to_owned()
+        ).await?;
+        manager.drop_foreign_key(ForeignKey::drop().table(UserSession::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(UserSession::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum UserSession {
+    Table,
+    Id,
+    UserId,
+    Ip,
+    UserAgent,
+    Revoked,
+    CreatedAt,
+    LastSeen,
+}
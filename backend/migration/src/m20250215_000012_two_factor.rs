@@ -0,0 +1,93 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(UserTotp::Table)
+                .if_not_exists()
+                .col(integer(UserTotp::UserId).primary_key())
+                .col(string_len(UserTotp::Secret, 64))
+                .col(boolean(UserTotp::Enabled).default(false))
+                .col(big_integer(UserTotp::LastStep).default(0))
+
+                .col(timestamp(UserTotp::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(UserTotp::Table, UserTotp::UserId)
+                .to(  UserInfo::Table, UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(RecoveryCode::Table)
+                .if_not_exists()
+                .col(pk_auto(RecoveryCode::Id))
+                .col(integer(RecoveryCode::UserId))
+                .col(string_len(RecoveryCode::CodeHash, 64))
+                .col(boolean(RecoveryCode::Used).default(false))
+
+                .col(timestamp(RecoveryCode::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(RecoveryCode::Table, RecoveryCode::UserId)
+                .to(  UserInfo::Table,     UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_recovery_code_user_id")
+                .table(RecoveryCode::Table)
+                .col(RecoveryCode::UserId)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_foreign_key(ForeignKey::drop().table(RecoveryCode::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(RecoveryCode::Table).to_owned()).await?;
+        manager.drop_foreign_key(ForeignKey::drop().table(UserTotp::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(UserTotp::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum UserTotp {
+    Table,
+    UserId,
+    Secret,
+    Enabled,
+    LastStep,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+pub enum RecoveryCode {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    Used,
+    CreatedAt,
+}
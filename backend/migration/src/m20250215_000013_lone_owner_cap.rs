@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+use crate::m20241006_000002_lone_info::LoneInfo;
+
+/// Name Postgres gave the `.unique_key()` of `m20241006_000002_lone_info`.
+const OWNER_ID_UNIQUE: &str = "lone_info_owner_id_key";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.get_connection().execute_unprepared(
+            &format!("ALTER TABLE lone_info DROP CONSTRAINT IF EXISTS {}", OWNER_ID_UNIQUE)
+        ).await?;
+
+        // The unique constraint doubled as the index for owner lookups.
+        manager.create_index(
+            Index::create()
+                .name("idx_lone_info_owner_id")
+                .table(LoneInfo::Table)
+                .col(LoneInfo::OwnerId)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(Index::drop().name("idx_lone_info_owner_id").table(LoneInfo::Table).to_owned()).await?;
+        manager.get_connection().execute_unprepared(
+            &format!("ALTER TABLE lone_info ADD CONSTRAINT {} UNIQUE (owner_id)", OWNER_ID_UNIQUE)
+        ).await?;
+        Ok(())
+    }
+}
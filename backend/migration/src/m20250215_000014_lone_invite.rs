@@ -0,0 +1,89 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20241006_000002_lone_info::LoneInfo;
+use crate::m20241006_000004_room_info::RoomInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(LoneInvite::Table)
+                .if_not_exists()
+                .col(string_len(LoneInvite::Code, 16).primary_key())
+                .col(integer(LoneInvite::LoneId))
+                .col(integer(LoneInvite::CreatorId))
+                .col(integer_null(LoneInvite::RoomId))
+                .col(integer_null(LoneInvite::MaxUses))
+                .col(integer(LoneInvite::Uses).default(0))
+                .col(timestamp_null(LoneInvite::ExpireAt))
+
+                .col(timestamp(LoneInvite::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_lone_id")
+                .from(LoneInvite::Table, LoneInvite::LoneId)
+                .to(  LoneInfo::Table,   LoneInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_creator_id")
+                .from(LoneInvite::Table, LoneInvite::CreatorId)
+                .to(  UserInfo::Table,   UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // Deleting the target room keeps the invite, it just lands in the lone.
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_room_id")
+                .from(LoneInvite::Table, LoneInvite::RoomId)
+                .to(  RoomInfo::Table,   RoomInfo::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_lone_invite_lone_id")
+                .table(LoneInvite::Table)
+                .col(LoneInvite::LoneId)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(Index::drop().name("idx_lone_invite_lone_id").table(LoneInvite::Table).to_owned()).await?;
+        manager.drop_foreign_key(ForeignKey::drop().table(LoneInvite::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(LoneInvite::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum LoneInvite {
+    Table,
+    Code,
+    LoneId,
+    CreatorId,
+    RoomId,
+    MaxUses,
+    Uses,
+    ExpireAt,
+    CreatedAt,
+}
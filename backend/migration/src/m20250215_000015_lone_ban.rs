@@ -0,0 +1,78 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20241006_000002_lone_info::LoneInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(LoneBan::Table)
+                .if_not_exists()
+                .col(integer(LoneBan::LoneId))
+                .col(integer(LoneBan::UserId))
+                .col(integer_null(LoneBan::ModeratorId))
+                .col(string_len_null(LoneBan::Reason, 256))
+                .col(timestamp_null(LoneBan::ExpireAt))
+
+                .col(timestamp(LoneBan::CreatedAt).default(Expr::current_timestamp()))
+                .primary_key(Index::create()
+                    .col(LoneBan::LoneId)
+                    .col(LoneBan::UserId))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_lone_id")
+                .from(LoneBan::Table,  LoneBan::LoneId)
+                .to(  LoneInfo::Table, LoneInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(LoneBan::Table,  LoneBan::UserId)
+                .to(  UserInfo::Table, UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // The ban outlives the moderator's account.
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_moderator_id")
+                .from(LoneBan::Table,  LoneBan::ModeratorId)
+                .to(  UserInfo::Table, UserInfo::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_foreign_key(ForeignKey::drop().table(LoneBan::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(LoneBan::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum LoneBan {
+    Table,
+    LoneId,
+    UserId,
+    ModeratorId,
+    Reason,
+    ExpireAt,
+    CreatedAt,
+}
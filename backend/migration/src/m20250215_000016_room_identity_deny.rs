@@ -0,0 +1,47 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000005_room_identity_info::RoomIdentityInfo;
+
+/// Room identities override the lone roles both ways: `allow` grants, `deny` takes away.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(RoomIdentityInfo::Table)
+                .rename_column(RoomIdentityInfo::Privilege, RoomIdentityDeny::Allow)
+                .to_owned()
+        ).await?;
+        manager.alter_table(
+            Table::alter()
+                .table(RoomIdentityInfo::Table)
+                .add_column(big_integer(RoomIdentityDeny::Deny).default(0))
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(RoomIdentityInfo::Table)
+                .drop_column(RoomIdentityDeny::Deny)
+                .to_owned()
+        ).await?;
+        manager.alter_table(
+            Table::alter()
+                .table(RoomIdentityInfo::Table)
+                .rename_column(RoomIdentityDeny::Allow, RoomIdentityInfo::Privilege)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RoomIdentityDeny {
+    Allow,
+    Deny,
+}
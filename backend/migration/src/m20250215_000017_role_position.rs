@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000003_lone_role_info::LoneRoleInfo;
+
+/// Roles are ranked by position within their lone, higher ranks above lower ones.
+/// "@everyone" sits at 0, other roles start at 1.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(LoneRoleInfo::Table)
+                .add_column(integer(RolePosition::Position).default(0))
+                .to_owned()
+        ).await?;
+
+        // Rank existing roles in the order they were created.
+        manager.get_connection().execute_unprepared(r#"
+            UPDATE lone_role_info AS role SET position = ranked.rn
+            FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY lone_id ORDER BY id) AS rn
+                FROM lone_role_info WHERE name <> '@everyone'
+            ) AS ranked
+            WHERE role.id = ranked.id
+        "#).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_lone_role_info_lone_id")
+                .table(LoneRoleInfo::Table)
+                .col(LoneRoleInfo::LoneId)
+                .col(RolePosition::Position)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(Index::drop().name("idx_lone_role_info_lone_id").table(LoneRoleInfo::Table).to_owned()).await?;
+        manager.alter_table(
+            Table::alter()
+                .table(LoneRoleInfo::Table)
+                .drop_column(RolePosition::Position)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RolePosition {
+    Position,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000004_room_info::RoomInfo;
+
+/// `room_type` was a single char the entity never matched, it now holds "text" or "voice".
+/// Rooms also get a position to be listed in.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.get_connection().execute_unprepared(r#"
+            ALTER TABLE room_info
+                ALTER COLUMN room_type DROP DEFAULT,
+                ALTER COLUMN room_type TYPE varchar(16)
+                    USING CASE WHEN room_type = '1' THEN 'voice' ELSE 'text' END,
+                ALTER COLUMN room_type SET DEFAULT 'text'
+        "#).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(RoomInfo::Table)
+                .add_column(integer(RoomOrder::Position).default(0))
+                .to_owned()
+        ).await?;
+
+        manager.get_connection().execute_unprepared(r#"
+            UPDATE room_info AS room SET position = ranked.rn
+            FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY lone_id ORDER BY id) - 1 AS rn
+                FROM room_info
+            ) AS ranked
+            WHERE room.id = ranked.id
+        "#).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(RoomInfo::Table)
+                .drop_column(RoomOrder::Position)
+                .to_owned()
+        ).await?;
+        manager.get_connection().execute_unprepared(r#"
+            ALTER TABLE room_info
+                ALTER COLUMN room_type DROP DEFAULT,
+                ALTER COLUMN room_type TYPE char(1)
+                    USING CASE WHEN room_type = 'voice' THEN '1' ELSE '0' END,
+                ALTER COLUMN room_type SET DEFAULT '0'
+        "#).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RoomOrder {
+    Position,
+}
@@ -0,0 +1,169 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20241006_000002_lone_info::LoneInfo;
+use crate::m20241006_000004_room_info::RoomInfo;
+
+/// Categories group the rooms of a lone. They carry identities like rooms do,
+/// whose overrides pass down to the rooms in them.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(RoomCategory::Table)
+                .if_not_exists()
+                .col(pk_auto(RoomCategory::Id))
+                .col(integer(RoomCategory::LoneId))
+                .col(string_len(RoomCategory::Name, 32))
+                .col(integer(RoomCategory::Position).default(0))
+
+                .col(timestamp(RoomCategory::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_lone_id")
+                .from(RoomCategory::Table, RoomCategory::LoneId)
+                .to(  LoneInfo::Table,     LoneInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // Deleting a category leaves its rooms uncategorized.
+        manager.alter_table(
+            Table::alter()
+                .table(RoomInfo::Table)
+                .add_column(integer_null(RoomCategoryRef::CategoryId))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_category_id")
+                .from(RoomInfo::Table,     RoomCategoryRef::CategoryId)
+                .to(  RoomCategory::Table, RoomCategory::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(CategoryIdentityInfo::Table)
+                .if_not_exists()
+                .col(pk_auto(CategoryIdentityInfo::Id))
+                .col(string_len(CategoryIdentityInfo::Name, 32))
+                .col(integer(CategoryIdentityInfo::CategoryId))
+                .col(big_integer(CategoryIdentityInfo::Allow).default(0))
+                .col(big_integer(CategoryIdentityInfo::Deny).default(0))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_category_id")
+                .from(CategoryIdentityInfo::Table, CategoryIdentityInfo::CategoryId)
+                .to(  RoomCategory::Table,         RoomCategory::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(AssocCategoryUser::Table).if_not_exists()
+                .col(integer(AssocCategoryUser::CategoryId))
+                .col(integer(AssocCategoryUser::UserId))
+                .col(integer(AssocCategoryUser::IdenId))
+                .primary_key(Index::create()
+                    .col(AssocCategoryUser::CategoryId)
+                    .col(AssocCategoryUser::UserId)
+                    .col(AssocCategoryUser::IdenId))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_category_id")
+                .from(AssocCategoryUser::Table, AssocCategoryUser::CategoryId)
+                .to(  RoomCategory::Table,      RoomCategory::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(AssocCategoryUser::Table, AssocCategoryUser::UserId)
+                .to(  UserInfo::Table,          UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_iden_id")
+                .from(AssocCategoryUser::Table,    AssocCategoryUser::IdenId)
+                .to(  CategoryIdentityInfo::Table, CategoryIdentityInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(AssocCategoryUser::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(CategoryIdentityInfo::Table).to_owned()).await?;
+        manager.alter_table(
+            Table::alter()
+                .table(RoomInfo::Table)
+                .drop_foreign_key(Alias::new("fk_category_id"))
+                .drop_column(RoomCategoryRef::CategoryId)
+                .to_owned()
+        ).await?;
+        manager.drop_table(Table::drop().table(RoomCategory::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum RoomCategory {
+    Table,
+    Id,
+    LoneId,
+    Name,
+    Position,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum RoomCategoryRef {
+    CategoryId,
+}
+
+#[derive(DeriveIden)]
+pub enum CategoryIdentityInfo {
+    Table,
+    Id,
+    Name,
+    CategoryId,
+    Allow,
+    Deny,
+}
+
+#[derive(DeriveIden)]
+pub enum AssocCategoryUser {
+    Table,
+    CategoryId,
+    UserId,
+    IdenId,
+}
@@ -0,0 +1,138 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20241006_000002_lone_info::LoneInfo;
+use crate::m20241006_000004_room_info::RoomInfo;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // A message is scoped either to a lone (and maybe one of its rooms), or to a private
+        // conversation with `peer_id`. No author means the system sent it.
+        manager.create_table(
+            Table::create()
+                .table(Message::Table)
+                .if_not_exists()
+                .col(pk_auto(Message::Id))
+                .col(integer_null(Message::AuthorId))
+                .col(integer_null(Message::LoneId))
+                .col(integer_null(Message::RoomId))
+                .col(integer_null(Message::PeerId))
+                .col(json_binary(Message::Content))
+                .col(integer_null(Message::QuoteId))
+
+                .col(timestamp(Message::CreatedAt).default(Expr::current_timestamp()))
+                .col(timestamp_null(Message::EditedAt))
+                .check(
+                    Expr::col(Message::LoneId).is_not_null().and(Expr::col(Message::PeerId).is_null())
+                        .or(Expr::col(Message::LoneId).is_null()
+                            .and(Expr::col(Message::RoomId).is_null())
+                            .and(Expr::col(Message::PeerId).is_not_null()))
+                )
+                .to_owned()
+        ).await?;
+
+        // Messages stay when their author's account goes away.
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_author_id")
+                .from(Message::Table,  Message::AuthorId)
+                .to(  UserInfo::Table, UserInfo::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_lone_id")
+                .from(Message::Table,  Message::LoneId)
+                .to(  LoneInfo::Table, LoneInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_room_id")
+                .from(Message::Table,  Message::RoomId)
+                .to(  RoomInfo::Table, RoomInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_peer_id")
+                .from(Message::Table,  Message::PeerId)
+                .to(  UserInfo::Table, UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // Deleting a quoted message only clears the quote.
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_quote_id")
+                .from(Message::Table, Message::QuoteId)
+                .to(  Message::Table, Message::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_message_room_id")
+                .table(Message::Table)
+                .col(Message::RoomId)
+                .col(Message::Id)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_message_lone_id")
+                .table(Message::Table)
+                .col(Message::LoneId)
+                .col(Message::Id)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_message_peer_id")
+                .table(Message::Table)
+                .col(Message::PeerId)
+                .col(Message::AuthorId)
+                .col(Message::Id)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Message::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Message {
+    Table,
+    Id,
+    AuthorId,
+    LoneId,
+    RoomId,
+    PeerId,
+    Content,
+    QuoteId,
+    CreatedAt,
+    EditedAt,
+}
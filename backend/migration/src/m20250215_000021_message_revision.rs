@@ -0,0 +1,92 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20250215_000020_message::Message;
+
+/// Edits and deletions keep what the message said before in `message_revision`.
+/// Deleted messages stay in `message` with `deleted_at` set.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Message::Table)
+                .add_column(timestamp_null(MessageDeletion::DeletedAt))
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(MessageRevision::Table)
+                .if_not_exists()
+                .col(pk_auto(MessageRevision::Id))
+                .col(integer(MessageRevision::MessageId))
+                .col(integer_null(MessageRevision::EditorId))
+                .col(string_len(MessageRevision::Action, 16))
+                .col(json_binary(MessageRevision::Content))
+
+                .col(timestamp(MessageRevision::CreatedAt).default(Expr::current_timestamp()))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_message_id")
+                .from(MessageRevision::Table, MessageRevision::MessageId)
+                .to(  Message::Table,         Message::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        // The audit trail outlives the editor's account.
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_editor_id")
+                .from(MessageRevision::Table, MessageRevision::EditorId)
+                .to(  UserInfo::Table,        UserInfo::Id)
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_message_revision_message_id")
+                .table(MessageRevision::Table)
+                .col(MessageRevision::MessageId)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(MessageRevision::Table).to_owned()).await?;
+        manager.alter_table(
+            Table::alter()
+                .table(Message::Table)
+                .drop_column(MessageDeletion::DeletedAt)
+                .to_owned()
+        ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum MessageDeletion {
+    DeletedAt,
+}
+
+#[derive(DeriveIden)]
+pub enum MessageRevision {
+    Table,
+    Id,
+    MessageId,
+    EditorId,
+    Action,
+    Content,
+    CreatedAt,
+}
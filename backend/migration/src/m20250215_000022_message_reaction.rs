@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, schema::*};
+use crate::m20241006_000001_user_info::UserInfo;
+use crate::m20250215_000020_message::Message;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // `emoji` is either the Unicode emoji itself or "custom:" followed by a lone emoji id.
+        manager.create_table(
+            Table::create()
+                .table(MessageReaction::Table)
+                .if_not_exists()
+                .col(integer(MessageReaction::MessageId))
+                .col(integer(MessageReaction::UserId))
+                .col(string_len(MessageReaction::Emoji, 64))
+
+                .col(timestamp(MessageReaction::CreatedAt).default(Expr::current_timestamp()))
+                .primary_key(Index::create()
+                    .col(MessageReaction::MessageId)
+                    .col(MessageReaction::UserId)
+                    .col(MessageReaction::Emoji))
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_message_id")
+                .from(MessageReaction::Table, MessageReaction::MessageId)
+                .to(  Message::Table,         Message::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_foreign_key(
+            ForeignKey::create()
+                .name("fk_user_id")
+                .from(MessageReaction::Table, MessageReaction::UserId)
+                .to(  UserInfo::Table,        UserInfo::Id)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_message_reaction_emoji")
+                .table(MessageReaction::Table)
+                .col(MessageReaction::MessageId)
+                .col(MessageReaction::Emoji)
+                .to_owned()
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(MessageReaction::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum MessageReaction {
+    Table,
+    MessageId,
+    UserId,
+    Emoji,
+    CreatedAt,
+}
@@ -1,3 +1,4 @@
+use std::fmt::Debug;
 use anyhow::{anyhow, Result};
 use lettre::{
     message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
@@ -6,26 +7,48 @@ use lettre::{
 use serde::Deserialize;
 use tokio;
 
+/// Outgoing mail used by the account flows, abstracted so tests can swap in a mock transport.
+#[async_trait::async_trait]
+pub trait Mailer: Send + Sync + Debug {
+    async fn send_verify_code(&self, to_addr: &str, code: u32) -> Result<()>;
+    async fn send_reset_token(&self, to_addr: &str, token: &str) -> Result<()>;
+}
+
+const DEFAULT_RESET_URL: &str = "http://localhost:55555/password/reset";
+
+fn default_reset_url() -> String {
+    DEFAULT_RESET_URL.to_string()
+}
+
 #[derive(Debug, Clone, Deserialize)]
-pub struct Email<'a> {
-    address:        &'a str,
-    password:       &'a str,
-    smtp_address:   &'a str,
+pub struct Email {
+    address:        String,
+    password:       String,
+    smtp_address:   String,
+    /// Page the password reset link points to, the token is passed as `?token=`.
+    #[serde(default = "default_reset_url")]
+    reset_url:      String,
 
     #[serde(skip)]
-    template:       &'a str,
+    template:       String,
 }
 
 
-impl<'a> Email<'a> {
+impl Email {
 
-    pub fn new(address: &'a str, password: &'a str, smtp_address: &'a str, template: &'a str) -> Self {
-        Self { address, password, smtp_address, template, }
+    pub fn new(address: &str, password: &str, smtp_address: &str, template: &str) -> Self {
+        Self {
+            address:        address.to_string(),
+            password:       password.to_string(),
+            smtp_address:   smtp_address.to_string(),
+            reset_url:      default_reset_url(),
+            template:       template.to_string(),
+        }
     }
 
-    pub fn from(config: &'a str, template: &'a str) -> Result<Self> {
+    pub fn from(config: &str, template: &str) -> Result<Self> {
         let mut ret = serde_json::from_str::<Email>(config)?;
-        ret.template = template;
+        ret.template = template.to_string();
         Ok(ret)
     }
 
@@ -46,9 +69,9 @@ impl<'a> Email<'a> {
         let creds = Credentials::new(self.address.to_string(), self.password.to_string());
 
         let mailer = if self.address.ends_with("@icloud.com") {
-            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(self.smtp_address)?
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_address)?
         } else {
-            AsyncSmtpTransport::<Tokio1Executor>::relay(self.smtp_address)?
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_address)?
         };
         let mailer = mailer.credentials(creds).build();
 
@@ -69,6 +92,66 @@ impl<'a> Email<'a> {
         .await?;
         Ok(())
     }
+
+    pub async fn send_reset_token(&self, to_addr: &str, token: &str) -> Result<()> {
+        let link = format!("{}?token={}", self.reset_url, token);
+        let body = format!(
+            "<p>Someone asked to reset the password of your ChatAlone account.</p>\
+             <p><a href=\"{link}\">Reset password</a></p>\
+             <p>The link can be used only once and expires shortly. \
+             If it wasn't you, just ignore this email.</p>"
+        );
+        self.send(
+            to_addr,
+            "[ChatAlone] Password Reset",
+            ContentType::TEXT_HTML,
+            body,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for Email {
+    async fn send_verify_code(&self, to_addr: &str, code: u32) -> Result<()> {
+        Email::send_verify_code(self, to_addr, code).await
+    }
+
+    async fn send_reset_token(&self, to_addr: &str, token: &str) -> Result<()> {
+        Email::send_reset_token(self, to_addr, token).await
+    }
+}
+
+/// Records every mail instead of sending it.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockMailer {
+    pub sent: std::sync::Mutex<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+impl MockMailer {
+    pub fn last_to(&self, to_addr: &str) -> Option<String> {
+        self.sent.lock().unwrap()
+            .iter().rev()
+            .find(|(to, _)| to == to_addr)
+            .map(|(_, content)| content.clone())
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Mailer for MockMailer {
+    async fn send_verify_code(&self, to_addr: &str, code: u32) -> Result<()> {
+        self.sent.lock().unwrap().push((to_addr.to_string(), code.to_string()));
+        Ok(())
+    }
+
+    async fn send_reset_token(&self, to_addr: &str, token: &str) -> Result<()> {
+        self.sent.lock().unwrap().push((to_addr.to_string(), token.to_string()));
+        Ok(())
+    }
 }
 
 
@@ -16,13 +16,13 @@ pub struct Model {
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
-        belongs_to = "super::lone_role_info::Entity",
-        from = "(Column::IdenId, Column::IdenId, Column::IdenId, Column::IdenId)",
-        to = "(super::lone_role_info::Column::Id, super::lone_role_info::Column::Id, super::lone_role_info::Column::Id, super::lone_role_info::Column::Id)",
+        belongs_to = "super::room_identity_info::Entity",
+        from = "Column::IdenId",
+        to = "super::room_identity_info::Column::Id",
         on_update = "Cascade",
         on_delete = "Cascade"
     )]
-    LoneRoleInfo,
+    RoomIdentityInfo,
     #[sea_orm(
         belongs_to = "super::room_info::Entity",
         from = "Column::RoomId",
@@ -41,9 +41,9 @@ pub enum Relation {
     UserInfo,
 }
 
-impl Related<super::lone_role_info::Entity> for Entity {
+impl Related<super::room_identity_info::Entity> for Entity {
     fn to() -> RelationDef {
-        Relation::LoneRoleInfo.def()
+        Relation::RoomIdentityInfo.def()
     }
 }
 
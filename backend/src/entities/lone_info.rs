@@ -11,7 +11,6 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub name: String,
-    #[sea_orm(unique)]
     pub owner_id: i32,
     pub created_at: DateTime,
 }
@@ -20,8 +19,16 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::assoc_lone_user::Entity")]
     AssocLoneUser,
+    #[sea_orm(has_many = "super::lone_ban::Entity")]
+    LoneBan,
+    #[sea_orm(has_many = "super::lone_invite::Entity")]
+    LoneInvite,
     #[sea_orm(has_many = "super::lone_role_info::Entity")]
     LoneRoleInfo,
+    #[sea_orm(has_many = "super::message::Entity")]
+    Message,
+    #[sea_orm(has_many = "super::room_category::Entity")]
+    RoomCategory,
     #[sea_orm(has_many = "super::room_info::Entity")]
     RoomInfo,
     #[sea_orm(
@@ -43,12 +50,36 @@ impl Related<super::assoc_lone_user::Entity> for Entity {
     }
 }
 
+impl Related<super::lone_ban::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoneBan.def()
+    }
+}
+
+impl Related<super::lone_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoneInvite.def()
+    }
+}
+
 impl Related<super::lone_role_info::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::LoneRoleInfo.def()
     }
 }
 
+impl Related<super::message::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Message.def()
+    }
+}
+
+impl Related<super::room_category::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomCategory.def()
+    }
+}
+
 impl Related<super::room_info::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::RoomInfo.def()
@@ -3,6 +3,7 @@
 use chrono::Utc;
 use sea_orm::ActiveValue;
 use sea_orm::entity::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use crate::id::{GeneralId, LoneId, RoleId, UserId};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -13,14 +14,13 @@ pub struct Model {
     pub name: String,
     pub lone_id: i32,
     pub privilege: i64,
+    pub position: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::assoc_lone_user::Entity")]
     AssocLoneUser,
-    #[sea_orm(has_many = "super::assoc_room_user::Entity")]
-    AssocRoomUser,
     #[sea_orm(
         belongs_to = "super::lone_info::Entity",
         from = "Column::LoneId",
@@ -37,12 +37,6 @@ impl Related<super::assoc_lone_user::Entity> for Entity {
     }
 }
 
-impl Related<super::assoc_room_user::Entity> for Entity {
-    fn to() -> RelationDef {
-        Relation::AssocRoomUser.def()
-    }
-}
-
 impl Related<super::lone_info::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::LoneInfo.def()
@@ -51,22 +45,104 @@ impl Related<super::lone_info::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
-#[derive(Copy, Clone, Debug)]
-pub struct RolePrivilege(u64);
+bitflags::bitflags! {
+    /// What members holding a role may do in their lone.
+    ///
+    /// The bits are stored in `lone_role_info.privilege`, so they must never be reordered.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct RolePrivilege: u64 {
+        const VIEW_ROOM         = 1 << 0;
+        const SEND_MESSAGES     = 1 << 1;
+        const MANAGE_MESSAGES   = 1 << 2;
+        const MANAGE_ROOMS      = 1 << 3;
+        const MANAGE_ROLES      = 1 << 4;
+        const KICK_MEMBERS      = 1 << 5;
+        const BAN_MEMBERS       = 1 << 6;
+        const MENTION_EVERYONE  = 1 << 7;
+        const ATTACH_FILES      = 1 << 8;
+        /// Implies every other privilege.
+        const ADMINISTRATOR     = 1 << 9;
+    }
+}
+
+/// Names used on the wire, in bit order.
+const PRIVILEGE_NAMES: [(&str, RolePrivilege); 10] = [
+    ("view_room",           RolePrivilege::VIEW_ROOM),
+    ("send_messages",       RolePrivilege::SEND_MESSAGES),
+    ("manage_messages",     RolePrivilege::MANAGE_MESSAGES),
+    ("manage_rooms",        RolePrivilege::MANAGE_ROOMS),
+    ("manage_roles",        RolePrivilege::MANAGE_ROLES),
+    ("kick_members",        RolePrivilege::KICK_MEMBERS),
+    ("ban_members",         RolePrivilege::BAN_MEMBERS),
+    ("mention_everyone",    RolePrivilege::MENTION_EVERYONE),
+    ("attach_files",        RolePrivilege::ATTACH_FILES),
+    ("administrator",       RolePrivilege::ADMINISTRATOR),
+];
+
+impl RolePrivilege {
+    /// What the "@everyone" role of a new lone gets.
+    pub fn everyone() -> Self {
+        RolePrivilege::VIEW_ROOM | RolePrivilege::SEND_MESSAGES | RolePrivilege::ATTACH_FILES
+    }
+
+    /// Whether every privilege in `other` is held, administrators hold them all.
+    pub fn allows(self, other: RolePrivilege) -> bool {
+        self.contains(RolePrivilege::ADMINISTRATOR) || self.contains(other)
+    }
+
+    pub fn grant(&mut self, other: RolePrivilege) {
+        self.insert(other);
+    }
+
+    pub fn revoke(&mut self, other: RolePrivilege) {
+        self.remove(other);
+    }
+
+    pub fn from_wire_name(name: &str) -> Option<Self> {
+        PRIVILEGE_NAMES.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, privilege)| *privilege)
+    }
+
+    /// Names of the privileges held, unknown bits are left out.
+    pub fn wire_names(self) -> Vec<&'static str> {
+        PRIVILEGE_NAMES.iter()
+            .filter(|(_, privilege)| self.contains(*privilege))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+impl Serialize for RolePrivilege {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.wire_names())
+    }
+}
+
+impl<'de> Deserialize<'de> for RolePrivilege {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(RolePrivilege::empty(), |acc, name| {
+            RolePrivilege::from_wire_name(name)
+                .map(|privilege| acc | privilege)
+                .ok_or_else(|| de::Error::custom(format!("unknown privilege `{}`", name)))
+        })
+    }
+}
 
 impl Into<u64> for RolePrivilege {
     fn into(self) -> u64 {
-        self.0
+        self.bits()
     }
 }
 impl Into<i64> for RolePrivilege {
     fn into(self) -> i64 {
-        self.0 as i64
+        self.bits() as i64
     }
 }
 impl From<u64> for RolePrivilege {
     fn from(val: u64) -> Self {
-        RolePrivilege(val)
+        RolePrivilege::from_bits_retain(val)
     }
 }
 impl From<i64> for RolePrivilege {
@@ -74,11 +150,6 @@ impl From<i64> for RolePrivilege {
         RolePrivilege::from(val as u64)
     }
 }
-impl Default for RolePrivilege {
-    fn default() -> Self {
-        RolePrivilege(0)
-    }
-}
 
 pub struct LoneRoleTable {
     is_customized:      bool,
@@ -133,14 +204,16 @@ impl Into<ActiveModel> for LoneRoleTable {
                 id:             ActiveValue::NotSet,
                 lone_id:        ActiveValue::Set(self.lone_id.decode() as i32),
                 name:           ActiveValue::Set(self.name),
-                privilege:      ActiveValue::Set(self.privilege.into())
+                privilege:      ActiveValue::Set(self.privilege.into()),
+                position:       ActiveValue::NotSet,
             }
         } else {
             ActiveModel {
                 id:             ActiveValue::Set(self.id.decode() as i32),
                 lone_id:        ActiveValue::Set(self.lone_id.decode() as i32),
                 name:           ActiveValue::Set(self.name),
-                privilege:      ActiveValue::Set(self.privilege.into())
+                privilege:      ActiveValue::Set(self.privilege.into()),
+                position:       ActiveValue::NotSet,
             }
         }
     }
@@ -152,7 +225,31 @@ impl Into<Model> for LoneRoleTable {
             id:             self.id.decode() as i32,
             lone_id:        self.lone_id.decode() as i32,
             name:           self.name,
-            privilege:      self.privilege.into()
+            privilege:      self.privilege.into(),
+            position:       0,
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn role_privilege_test() {
+    let mut privilege = RolePrivilege::everyone();
+    assert!(privilege.allows(RolePrivilege::VIEW_ROOM | RolePrivilege::SEND_MESSAGES));
+    assert!(!privilege.allows(RolePrivilege::KICK_MEMBERS));
+    privilege.grant(RolePrivilege::KICK_MEMBERS);
+    privilege.revoke(RolePrivilege::ATTACH_FILES);
+    assert!(privilege.allows(RolePrivilege::KICK_MEMBERS));
+    assert!(!privilege.allows(RolePrivilege::ATTACH_FILES));
+    assert!(RolePrivilege::ADMINISTRATOR.allows(RolePrivilege::all()));
+
+    let json = serde_json::to_value(privilege).unwrap();
+    assert_eq!(json, serde_json::json!(["view_room", "send_messages", "kick_members"]));
+    assert_eq!(serde_json::from_value::<RolePrivilege>(json).unwrap(), privilege);
+    assert!(serde_json::from_str::<RolePrivilege>(r#"["fly"]"#).is_err());
+
+    // stored as i64 in the database
+    let stored: i64 = privilege.into();
+    assert_eq!(RolePrivilege::from(stored), privilege);
+    assert_eq!(PRIVILEGE_NAMES.len(), RolePrivilege::all().iter().count());
+}
@@ -2,10 +2,23 @@
 
 pub mod prelude;
 
+pub mod assoc_category_user;
 pub mod assoc_lone_user;
 pub mod assoc_room_user;
+pub mod category_identity_info;
+pub mod lone_ban;
 pub mod lone_info;
+pub mod lone_invite;
 pub mod lone_role_info;
+pub mod message;
+pub mod message_reaction;
+pub mod message_revision;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod room_category;
 pub mod room_identity_info;
 pub mod room_info;
+pub mod token_revocation;
 pub mod user_info;
+pub mod user_session;
+pub mod user_totp;
@@ -2,8 +2,20 @@
 
 pub use super::assoc_lone_user::Entity as AssocLoneUser;
 pub use super::assoc_room_user::Entity as AssocRoomUser;
+pub use super::category_identity_info::Entity as CategoryIdentityInfo;
+pub use super::lone_ban::Entity as LoneBan;
 pub use super::lone_info::Entity as LoneInfo;
+pub use super::lone_invite::Entity as LoneInvite;
 pub use super::lone_role_info::Entity as LoneRoleInfo;
+pub use super::message::Entity as Message;
+pub use super::message_reaction::Entity as MessageReaction;
+pub use super::message_revision::Entity as MessageRevision;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::room_category::Entity as RoomCategory;
 pub use super::room_identity_info::Entity as RoomIdentityInfo;
 pub use super::room_info::Entity as RoomInfo;
+pub use super::token_revocation::Entity as TokenRevocation;
 pub use super::user_info::Entity as UserInfo;
+pub use super::user_session::Entity as UserSession;
+pub use super::user_totp::Entity as UserTotp;
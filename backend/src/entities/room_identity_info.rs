@@ -9,11 +9,14 @@ pub struct Model {
     pub id: i32,
     pub name: String,
     pub room_id: i32,
-    pub privilege: i64,
+    pub allow: i64,
+    pub deny: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::assoc_room_user::Entity")]
+    AssocRoomUser,
     #[sea_orm(
         belongs_to = "super::room_info::Entity",
         from = "Column::RoomId",
@@ -24,6 +27,12 @@ pub enum Relation {
     RoomInfo,
 }
 
+impl Related<super::assoc_room_user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AssocRoomUser.def()
+    }
+}
+
 impl Related<super::room_info::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::RoomInfo.def()
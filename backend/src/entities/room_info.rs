@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
+use std::str::FromStr;
 use chrono::{NaiveDateTime, Utc};
 use sea_orm::ActiveValue;
 use sea_orm::entity::prelude::*;
@@ -12,14 +13,18 @@ pub struct Model {
     pub id: i32,
     pub lone_id: i32,
     pub name: String,
-    pub r#type: String,
+    pub room_type: String,
     pub created_at: DateTime,
+    pub position: i32,
+    pub category_id: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::assoc_room_user::Entity")]
     AssocRoomUser,
+    #[sea_orm(has_many = "super::lone_invite::Entity")]
+    LoneInvite,
     #[sea_orm(
         belongs_to = "super::lone_info::Entity",
         from = "Column::LoneId",
@@ -28,6 +33,16 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     LoneInfo,
+    #[sea_orm(has_many = "super::message::Entity")]
+    Message,
+    #[sea_orm(
+        belongs_to = "super::room_category::Entity",
+        from = "Column::CategoryId",
+        to = "super::room_category::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
+    RoomCategory,
     #[sea_orm(has_many = "super::room_identity_info::Entity")]
     RoomIdentityInfo,
 }
@@ -44,6 +59,24 @@ impl Related<super::lone_info::Entity> for Entity {
     }
 }
 
+impl Related<super::lone_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoneInvite.def()
+    }
+}
+
+impl Related<super::message::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Message.def()
+    }
+}
+
+impl Related<super::room_category::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomCategory.def()
+    }
+}
+
 impl Related<super::room_identity_info::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::RoomIdentityInfo.def()
@@ -53,17 +86,24 @@ impl Related<super::room_identity_info::Entity> for Entity {
 impl ActiveModelBehavior for ActiveModel {}
 
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RoomType {
     Text,
     Voice,
 }
 
-impl From<&str> for RoomType {
-    fn from(s: &str) -> Self {
+#[derive(Debug, thiserror::Error)]
+#[error("invalid room type `{0}`")]
+pub struct InvalidRoomType(pub String);
+
+impl FromStr for RoomType {
+    type Err = InvalidRoomType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "text" => RoomType::Text,
-            "voice" => RoomType::Voice,
-            _ => panic!("Invalid room type"),
+            "text" => Ok(RoomType::Text),
+            "voice" => Ok(RoomType::Voice),
+            _ => Err(InvalidRoomType(s.to_string())),
         }
     }
 }
@@ -122,16 +162,18 @@ impl RoomTable {
 }
 
 
-impl From<Model> for RoomTable {
-    fn from(model: Model) -> Self {
-        RoomTable {
+impl TryFrom<Model> for RoomTable {
+    type Error = InvalidRoomType;
+
+    fn try_from(model: Model) -> Result<Self, Self::Error> {
+        Ok(RoomTable {
             is_customized:  false,
             room_id:        RoomId::from_decoded(model.id as u32),
             lone_id:        RoomId::from_decoded(model.lone_id as u32),
-            room_type:      RoomType::from(model.r#type.as_ref()),
+            room_type:      model.room_type.parse()?,
             room_name:      model.name,
             created_at:     model.created_at,
-        }
+        })
     }
 }
 
@@ -142,16 +184,20 @@ impl Into<ActiveModel> for RoomTable {
                 id:         ActiveValue::NotSet,
                 lone_id:    ActiveValue::Set(self.lone_id.decode() as i32),
                 name:       ActiveValue::Set(self.room_name),
-                r#type:     ActiveValue::Set(self.room_type.into()),
+                room_type:  ActiveValue::Set(self.room_type.into()),
                 created_at: ActiveValue::NotSet,
+                position:   ActiveValue::NotSet,
+                category_id: ActiveValue::NotSet,
             }
         } else {
             ActiveModel {
                 id:         ActiveValue::Set(self.room_id.decode() as i32),
                 lone_id:    ActiveValue::Set(self.lone_id.decode() as i32),
                 name:       ActiveValue::Set(self.room_name),
-                r#type:     ActiveValue::Set(self.room_type.into()),
+                room_type:  ActiveValue::Set(self.room_type.into()),
                 created_at: ActiveValue::Set(self.created_at),
+                position:   ActiveValue::NotSet,
+                category_id: ActiveValue::NotSet,
             }
         }
     }
@@ -163,8 +209,18 @@ impl Into<Model> for RoomTable {
             id:         self.room_id.decode() as i32,
             lone_id:    self.lone_id.decode() as i32,
             name:       self.room_name,
-            r#type:     self.room_type.into(),
+            room_type:  self.room_type.into(),
             created_at: self.created_at,
+            position:   0,
+            category_id: None,
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn room_type_test() {
+    assert_eq!("text".parse::<RoomType>().unwrap(), RoomType::Text);
+    assert_eq!("voice".parse::<RoomType>().unwrap(), RoomType::Voice);
+    assert!("video".parse::<RoomType>().is_err());
+    assert_eq!(Into::<String>::into(RoomType::Voice), "voice");
+}
@@ -4,6 +4,7 @@ use chrono::Utc;
 use sea_orm::ActiveValue;
 use sea_orm::entity::prelude::*;
 use crate::id::{GeneralId, UserId};
+use crate::password;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(schema_name = "chat", table_name = "user_info")]
@@ -19,12 +20,36 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::assoc_category_user::Entity")]
+    AssocCategoryUser,
     #[sea_orm(has_many = "super::assoc_lone_user::Entity")]
     AssocLoneUser,
     #[sea_orm(has_many = "super::assoc_room_user::Entity")]
     AssocRoomUser,
     #[sea_orm(has_many = "super::lone_info::Entity")]
     LoneInfo,
+    #[sea_orm(has_many = "super::lone_invite::Entity")]
+    LoneInvite,
+    #[sea_orm(has_many = "super::message_reaction::Entity")]
+    MessageReaction,
+    #[sea_orm(has_many = "super::message_revision::Entity")]
+    MessageRevision,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+    #[sea_orm(has_many = "super::token_revocation::Entity")]
+    TokenRevocation,
+    #[sea_orm(has_many = "super::user_session::Entity")]
+    UserSession,
+    #[sea_orm(has_one = "super::user_totp::Entity")]
+    UserTotp,
+}
+
+impl Related<super::assoc_category_user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AssocCategoryUser.def()
+    }
 }
 
 impl Related<super::assoc_lone_user::Entity> for Entity {
@@ -51,6 +76,54 @@ impl Related<super::lone_info::Entity> for Entity {
     }
 }
 
+impl Related<super::lone_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoneInvite.def()
+    }
+}
+
+impl Related<super::message_reaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageReaction.def()
+    }
+}
+
+impl Related<super::message_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageRevision.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
+impl Related<super::token_revocation::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TokenRevocation.def()
+    }
+}
+
+impl Related<super::user_session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSession.def()
+    }
+}
+
+impl Related<super::user_totp::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserTotp.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
 
 
@@ -79,8 +152,8 @@ impl UserTable {
         Self::from_val(email, username, password, 0, Utc::now().naive_utc())
     }
 
-    pub fn verify_password(&self, password: &String) -> bool {
-        self.password.eq(password)
+    pub fn verify_password(&self, password: &str) -> bool {
+        password::verify_blocking(password, &self.password).is_valid()
     }
 
     pub fn uid(&self) -> UserId {
@@ -69,6 +69,8 @@ pub type UserId = Id;
 pub type RoomId = Id;
 pub type LoneId = Id;
 pub type RoleId = Id;
+pub type CategoryId = Id;
+pub type MessageId = Id;
 
 
 #[test]
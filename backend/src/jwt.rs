@@ -1,14 +1,18 @@
-use std::cell::{RefCell, RefMut};
-use std::fmt::{Display, Formatter};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
 use std::future::Future;
-use std::ops::Deref;
 
-use base64::{engine::general_purpose::URL_SAFE, Engine as _};
-use crypto::hmac::Hmac;
-use crypto::mac::Mac;
-use crypto::sha2::Sha256;
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine as _};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, UnparsedPublicKey,
+    ECDSA_P256_SHA256_FIXED, ECDSA_P256_SHA256_FIXED_SIGNING, ED25519,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use chrono::Utc;
+use nanoid::nanoid;
 
 use anyhow::{anyhow, Result};
 
@@ -22,11 +26,273 @@ use axum_extra::{
 };
 use serde_json::json;
 
-const JWT_SECRET: &str = "test_secret";
+use crate::server::AppState;
 
-#[derive(Debug, Serialize, Deserialize)]
+const JWT_KEY_GRACE_PERIOD: i64 = 7 * 24 * 3600;
+const JWT_SECRET_MIN_LEN: usize = 32;
+const JWT_ISSUER: &str = "chatalone";
+const JWT_AUDIENCE: &str = "chatalone";
+/// Seconds of clock skew tolerated on `exp` and `nbf`.
+const JWT_LEEWAY: i64 = 60;
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum JwtAlg {
+    #[default]
     HS256,
+    EdDSA,
+    ES256,
+}
+
+/// A signing key loaded from `cfg/jwt.json`.
+///
+/// `HS256` keys take a `secret`. `EdDSA` (Ed25519) and `ES256` (P-256) keys take a
+/// base64 encoded PKCS#8 DER `private_key`, their public halves are served as JWKS.
+#[derive(Clone, Deserialize)]
+pub struct JwtKey {
+    kid:            String,
+    #[serde(default)]
+    alg:            JwtAlg,
+    #[serde(default)]
+    secret:         Option<String>,
+    #[serde(default)]
+    private_key:    Option<String>,
+    /// Unix time (s) after which the key stops signing.
+    /// Tokens it signed are still accepted until the grace period is over.
+    #[serde(default)]
+    expire_at:      Option<i64>,
+}
+
+impl Debug for JwtKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("alg", &self.alg)
+            .field("expire_at", &self.expire_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JwtKey {
+    fn is_signing(&self, now: i64) -> bool {
+        self.expire_at.is_none_or(|t| now < t)
+    }
+
+    fn is_retired(&self, now: i64, grace_period: i64) -> bool {
+        self.expire_at.is_some_and(|t| now >= t + grace_period)
+    }
+}
+
+enum KeyMaterial {
+    Hmac(hmac::Key),
+    EdDSA(Ed25519KeyPair),
+    ES256(EcdsaKeyPair),
+}
+
+impl Debug for KeyMaterial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyMaterial::Hmac(_) => write!(f, "Hmac"),
+            KeyMaterial::EdDSA(_) => write!(f, "EdDSA"),
+            KeyMaterial::ES256(_) => write!(f, "ES256"),
+        }
+    }
+}
+
+impl KeyMaterial {
+    fn from_config(key: &JwtKey) -> Result<Self> {
+        let private_key = || -> Result<Vec<u8>> {
+            let der = key.private_key.as_ref()
+                .ok_or(anyhow!("JWT key `{}` has no private_key", key.kid))?;
+            Ok(STANDARD.decode(der.trim())?)
+        };
+        match key.alg {
+            JwtAlg::HS256 => {
+                let secret = key.secret.as_ref()
+                    .ok_or(anyhow!("JWT key `{}` has no secret", key.kid))?;
+                if secret.len() < JWT_SECRET_MIN_LEN {
+                    return Err(anyhow!("JWT key `{}` is shorter than {} bytes", key.kid, JWT_SECRET_MIN_LEN));
+                }
+                Ok(KeyMaterial::Hmac(hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())))
+            },
+            JwtAlg::EdDSA => {
+                // OpenSSL writes v1 PKCS#8 documents without the public key.
+                let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&private_key()?)
+                    .map_err(|e| anyhow!("Invalid EdDSA key `{}`: {}", key.kid, e))?;
+                Ok(KeyMaterial::EdDSA(pair))
+            },
+            JwtAlg::ES256 => {
+                let pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING, &private_key()?, &SystemRandom::new(),
+                ).map_err(|e| anyhow!("Invalid ES256 key `{}`: {}", key.kid, e))?;
+                Ok(KeyMaterial::ES256(pair))
+            },
+        }
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            KeyMaterial::Hmac(key) => Ok(hmac::sign(key, data).as_ref().to_vec()),
+            KeyMaterial::EdDSA(pair) => Ok(pair.sign(data).as_ref().to_vec()),
+            KeyMaterial::ES256(pair) => {
+                let signature = pair.sign(&SystemRandom::new(), data)
+                    .map_err(|_| anyhow!("Failed to sign with ES256"))?;
+                Ok(signature.as_ref().to_vec())
+            },
+        }
+    }
+
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        match self {
+            KeyMaterial::Hmac(key) => hmac::verify(key, data, signature).is_ok(),
+            KeyMaterial::EdDSA(pair)
+                => UnparsedPublicKey::new(&ED25519, pair.public_key()).verify(data, signature).is_ok(),
+            KeyMaterial::ES256(pair)
+                => UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, pair.public_key()).verify(data, signature).is_ok(),
+        }
+    }
+
+    /// The public half as a JWK, `None` for shared secrets.
+    fn public_jwk(&self) -> Option<Value> {
+        match self {
+            KeyMaterial::Hmac(_) => None,
+            KeyMaterial::EdDSA(pair) => Some(json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": URL_SAFE_NO_PAD.encode(pair.public_key()),
+            })),
+            KeyMaterial::ES256(pair) => {
+                // uncompressed point: 0x04 || x || y
+                let point = pair.public_key().as_ref();
+                Some(json!({
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+                    "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+                }))
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtConfig {
+    keys:           Vec<JwtKey>,
+    /// Seconds an expired key keeps verifying tokens, should outlive any token it signed.
+    #[serde(default = "default_grace_period")]
+    grace_period:   i64,
+    #[serde(default = "default_issuer")]
+    issuer:         String,
+    #[serde(default = "default_audience")]
+    audience:       String,
+    #[serde(default = "default_leeway")]
+    leeway:         i64,
+}
+
+fn default_grace_period() -> i64 {
+    JWT_KEY_GRACE_PERIOD
+}
+
+fn default_issuer() -> String {
+    JWT_ISSUER.to_string()
+}
+
+fn default_audience() -> String {
+    JWT_AUDIENCE.to_string()
+}
+
+fn default_leeway() -> i64 {
+    JWT_LEEWAY
+}
+
+#[derive(Debug)]
+struct LoadedKey {
+    config:     JwtKey,
+    material:   KeyMaterial,
+}
+
+/// The set of keys tokens may be signed with.
+///
+/// New tokens are signed with the last key in config order that hasn't expired,
+/// so a key can be rotated by appending its successor and giving it an `expire_at`.
+#[derive(Debug)]
+pub struct JwtKeys {
+    order:          Vec<String>,
+    keys:           HashMap<String, LoadedKey>,
+    grace_period:   i64,
+    issuer:         String,
+    audience:       String,
+    leeway:         i64,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: JwtConfig) -> Result<Self> {
+        let now = Utc::now().timestamp();
+        let mut order = vec![];
+        let mut keys = HashMap::new();
+        for key in config.keys {
+            let material = KeyMaterial::from_config(&key)?;
+            if key.is_retired(now, config.grace_period) {
+                println!("[JWT] Key `{}` is retired, skipped.", key.kid);
+                continue;
+            }
+            if keys.contains_key(&key.kid) {
+                return Err(anyhow!("Duplicated JWT key `{}`", key.kid));
+            }
+            order.push(key.kid.clone());
+            keys.insert(key.kid.clone(), LoadedKey { config: key, material });
+        }
+
+        let ret = Self {
+            order,
+            keys,
+            grace_period:   config.grace_period,
+            issuer:         config.issuer,
+            audience:       config.audience,
+            leeway:         config.leeway,
+        };
+        ret.signing_key()?;
+        Ok(ret)
+    }
+
+    fn signing_key(&self) -> Result<&LoadedKey> {
+        let now = Utc::now().timestamp();
+        self.order.iter().rev()
+            .filter_map(|kid| self.keys.get(kid))
+            .find(|key| key.config.is_signing(now))
+            .ok_or(anyhow!("No JWT key available for signing"))
+    }
+
+    fn verifying_key(&self, kid: &str) -> Option<&LoadedKey> {
+        let now = Utc::now().timestamp();
+        self.keys.get(kid).filter(|key| !key.config.is_retired(now, self.grace_period))
+    }
+
+    /// Signs `data` with the current signing key, for tokens other than JWTs.
+    /// Returns the kid together with the signature.
+    pub fn sign(&self, data: &[u8]) -> Result<(String, Vec<u8>)> {
+        let key = self.signing_key()?;
+        Ok((key.config.kid.clone(), key.material.sign(data)?))
+    }
+
+    pub fn verify(&self, kid: &str, data: &[u8], signature: &[u8]) -> bool {
+        self.verifying_key(kid)
+            .is_some_and(|key| key.material.verify(data, signature))
+    }
+
+    /// Public keys of every asymmetric key still accepted, as a JWK set.
+    pub fn jwks(&self) -> Value {
+        let keys = self.order.iter()
+            .filter_map(|kid| self.verifying_key(kid))
+            .filter_map(|key| {
+                let mut jwk = key.material.public_jwk()?;
+                jwk["kid"] = json!(key.config.kid);
+                jwk["alg"] = json!(key.config.alg);
+                jwk["use"] = json!("sig");
+                Some(jwk)
+            })
+            .collect::<Vec<_>>();
+        json!({ "keys": keys })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,176 +304,158 @@ pub enum JwtTyp {
 pub struct JwtHeader {
     alg:    JwtAlg,
     typ:    JwtTyp,
+    kid:    String,
 }
 impl JwtHeader {
-    fn default() -> Self {
+    fn new(alg: JwtAlg, kid: &str) -> Self {
         JwtHeader {
-            alg: JwtAlg::HS256,
+            alg,
             typ: JwtTyp::JWT,
+            kid: kid.to_string(),
         }
     }
 }
 
+/// RFC 7519 registered claims, times are in seconds. `sid` is the login session.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JwtPayload {
-    pub user_id:    usize,
-    pub exp_time:   i64,
+    pub sub:    String,
+    pub iss:    String,
+    pub aud:    String,
+    pub iat:    i64,
+    pub nbf:    i64,
+    pub exp:    i64,
+    pub jti:    String,
+    pub sid:    String,
 }
 impl JwtPayload {
-    fn new(user_id: usize, expire_time_s: i64) -> Self {
-        let time = Utc::now();
+    fn new(user_id: usize, session_id: &str, expire_time_s: i64, keys: &JwtKeys) -> Self {
+        let now = Utc::now().timestamp();
         JwtPayload {
-            user_id,
-            exp_time: time.timestamp_millis() + expire_time_s * 1000,
+            sub: user_id.to_string(),
+            iss: keys.issuer.clone(),
+            aud: keys.audience.clone(),
+            iat: now,
+            nbf: now,
+            exp: now + expire_time_s,
+            jti: nanoid!(),
+            sid: session_id.to_string(),
         }
     }
 }
 
 pub type JwtSignature = Vec<u8>;
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct Jwt {
-    header:     JwtHeader,
-    payload:    JwtPayload,
-    signature:  JwtSignature,
-
-    #[serde(skip_serializing)]
-    hmac_handler: RefCell<Hmac<Sha256>>,
+    header:         JwtHeader,
+    payload:        JwtPayload,
+    signature:      JwtSignature,
+    /// `<header>.<payload>` exactly as received, which is what the signature covers.
+    #[serde(skip)]
+    signing_input:  String,
 }
 
 impl Jwt {
-    fn new(user_id: usize, expire_duration_s: i64) -> Result<Self> {
-        let mut handler = RefCell::new(Hmac::<Sha256>::new(Sha256::new(), JWT_SECRET.as_bytes()));
-        let handle = handler.borrow_mut();
-
-        let header = JwtHeader::default();
-        let payload = JwtPayload::new(user_id, expire_duration_s);
-        let signature = Self::generate_signature(handle, &header, &payload)?;
+    fn new(user_id: usize, session_id: &str, expire_duration_s: i64, keys: &JwtKeys) -> Result<Self> {
+        let key = keys.signing_key()?;
+
+        let header = JwtHeader::new(key.config.alg, &key.config.kid);
+        let payload = JwtPayload::new(user_id, session_id, expire_duration_s, keys);
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_string(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_string(&payload)?),
+        );
+        let signature = key.material.sign(signing_input.as_bytes())?;
 
         Ok(Self {
             header,
             payload,
             signature,
-            hmac_handler: handler,
+            signing_input,
         })
     }
-    
+
+    /// The encoded user id carried in `sub`.
     pub fn user_id(&self) -> usize {
-        self.payload.user_id
+        self.payload.sub.parse().unwrap_or_default()
     }
-    
+
+    /// Unix time (s)
     pub fn expire_time(&self) -> i64 {
-        self.payload.exp_time
+        self.payload.exp
     }
-    
-    fn encode(&self) -> Result<String> {
-        Ok(format!(
-            "{}.{}.{}",
-            self.header_b64()?,
-            self.payload_b64()?,
-            self.signature_b64()
-        ))
+
+    /// Unix time (s)
+    pub fn issued_at(&self) -> i64 {
+        self.payload.iat
+    }
+
+    pub fn jti(&self) -> &str {
+        &self.payload.jti
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.payload.sid
     }
 
-    pub fn generate(user_id: usize, expire_duration_s: i64) -> Result<String> {
-        Jwt::new(user_id, expire_duration_s)?.encode()
+    fn encode(&self) -> String {
+        format!("{}.{}", self.signing_input, URL_SAFE_NO_PAD.encode(&self.signature))
     }
 
-    pub fn verify(&self) -> Result<(), JwtError> {
-        let result = Self::generate_signature(self.handler_mut(), self.header(), self.payload())
-            .map_err(|_| JwtError::InternalError("Failed to generate signature".into()))?;
+    pub fn generate(
+        user_id: usize, session_id: &str, expire_duration_s: i64, keys: &JwtKeys
+    ) -> Result<String> {
+        Ok(Jwt::new(user_id, session_id, expire_duration_s, keys)?.encode())
+    }
 
-        if !result.eq(self.signature()) {
+    pub fn verify(&self, keys: &JwtKeys) -> Result<(), JwtError> {
+        let key = keys.verifying_key(&self.header.kid).ok_or(JwtError::InvalidToken)?;
+        // The key decides the algorithm, never the token.
+        if key.config.alg != self.header.alg {
+            return Err(JwtError::InvalidToken);
+        }
+        if !key.material.verify(self.signing_input.as_bytes(), &self.signature) {
             return Err(JwtError::InvalidToken);
         }
 
-        let time = Utc::now();
-        if time.timestamp_millis() > self.payload.exp_time {
-            return Err(JwtError::Expired(self.payload.exp_time));
+        let payload = self.payload();
+        if payload.iss != keys.issuer || payload.aud != keys.audience {
+            return Err(JwtError::InvalidToken);
+        }
+        if payload.sub.parse::<usize>().is_err() {
+            return Err(JwtError::InvalidToken);
+        }
+
+        let now = Utc::now().timestamp();
+        if now > payload.exp + keys.leeway {
+            return Err(JwtError::Expired(payload.exp));
+        }
+        if now + keys.leeway < payload.nbf {
+            return Err(JwtError::InvalidToken);
         }
 
         Ok(())
     }
 
-    pub fn parse_and_verify(s: &str) -> Result<Self, JwtError> {
+    /// Accepts tokens signed by any key in `keys` that hasn't been retired yet.
+    pub fn parse_and_verify(s: &str, keys: &JwtKeys) -> Result<Self, JwtError> {
         let jwt: Jwt = s.try_into().map_err(|_| JwtError::InvalidToken)?;
-        jwt.verify()?;
+        jwt.verify(keys)?;
         Ok(jwt)
     }
 
-    fn generate_signature(
-        mut handler: RefMut<Hmac<Sha256>>,
-        h: &JwtHeader,
-        p: &JwtPayload,
-    ) -> Result<JwtSignature> {
-        let h_json = serde_json::to_string(h)?;
-        let p_json = serde_json::to_string(p)?;
-
-        let header_b64 = URL_SAFE.encode(h_json);
-        let payload_b64 = URL_SAFE.encode(p_json);
-
-        let content = format!("{header_b64}.{payload_b64}");
-        handler.input(content.as_bytes());
-        let signature = handler.result().code().to_vec();
-        handler.reset();
-
-        Ok(signature)
-    }
-
-    fn header_b64(&self) -> Result<String> {
-        let h_json = serde_json::to_string(self.header())?;
-        Ok(URL_SAFE.encode(h_json))
-    }
-    fn payload_b64(&self) -> Result<String> {
-        let p_json = serde_json::to_string(self.payload())?;
-        Ok(URL_SAFE.encode(p_json))
-    }
-    fn signature_b64(&self) -> String {
-        URL_SAFE.encode(&self.signature)
-    }
-
-    fn header(&self) -> &JwtHeader {
-        &self.header
-    }
     pub fn payload(&self) -> &JwtPayload {
         &self.payload
     }
-    fn signature(&self) -> &JwtSignature {
-        &self.signature
-    }
-    fn signature_str(&self) -> String {
-        String::from_utf8_lossy(self.signature.as_slice()).to_string()
-    }
-    fn handler_mut(&self) -> RefMut<Hmac<Sha256>> {
-        self.hmac_handler.borrow_mut()
-    }
 }
 
 impl TryInto<Jwt> for String {
     type Error = anyhow::Error;
 
     fn try_into(self) -> std::result::Result<Jwt, Self::Error> {
-        let jwt_vec: Vec<&str> = self.split(".").collect();
-        if jwt_vec.len() != 3 {
-            return Err(anyhow!("Invalid JWT."));
-        }
-        let header_json_u8 = URL_SAFE.decode(jwt_vec[0])?;
-        let payload_json_u8 = URL_SAFE.decode(jwt_vec[1])?;
-        let header_json = String::from_utf8_lossy(header_json_u8.as_slice());
-        let payload_json = String::from_utf8_lossy(payload_json_u8.as_slice());
-        let signature = URL_SAFE.decode(jwt_vec[2])?;
-
-        let header: JwtHeader = serde_json::from_str(header_json.deref())?;
-        let payload: JwtPayload = serde_json::from_str(payload_json.deref())?;
-        let mut hmac = Hmac::<Sha256>::new(Sha256::new(), JWT_SECRET.as_bytes());
-        hmac.reset();
-
-        Ok(Jwt {
-            header,
-            payload,
-            signature,
-            hmac_handler: RefCell::new(hmac),
-        })
+        Jwt::try_from(self.as_str())
     }
 }
 
@@ -215,27 +463,21 @@ impl TryFrom<&str> for Jwt {
     type Error = anyhow::Error;
 
     fn try_from(s: &str) -> std::result::Result<Self, Self::Error> {
-        let jwt_str = String::from(s);
-        let jwt_vec: Vec<&str> = jwt_str.split(".").collect();
-        if jwt_vec.len() != 3 {
+        let (signing_input, signature) = s.rsplit_once('.').ok_or(anyhow!("Invalid JWT."))?;
+        let (header_b64, payload_b64) = signing_input.split_once('.').ok_or(anyhow!("Invalid JWT."))?;
+        if payload_b64.contains('.') {
             return Err(anyhow!("Invalid JWT."));
         }
-        let header_json_u8 = URL_SAFE.decode(jwt_vec[0])?;
-        let payload_json_u8 = URL_SAFE.decode(jwt_vec[1])?;
-        let header_json = String::from_utf8_lossy(header_json_u8.as_slice());
-        let payload_json = String::from_utf8_lossy(payload_json_u8.as_slice());
-        let signature = URL_SAFE.decode(jwt_vec[2])?;
 
-        let header: JwtHeader = serde_json::from_str(header_json.deref())?;
-        let payload: JwtPayload = serde_json::from_str(payload_json.deref())?;
-        let mut hmac = Hmac::<Sha256>::new(Sha256::new(), JWT_SECRET.as_bytes());
-        hmac.reset();
+        let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header_b64)?)?;
+        let payload: JwtPayload = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload_b64)?)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature)?;
 
         Ok(Jwt {
             header,
             payload,
             signature,
-            hmac_handler: RefCell::new(hmac),
+            signing_input: signing_input.to_string(),
         })
     }
 }
@@ -243,12 +485,7 @@ impl TryFrom<&str> for Jwt {
 impl TryInto<String> for Jwt {
     type Error = anyhow::Error;
     fn try_into(self) -> Result<String, Self::Error> {
-        Ok(format!(
-            "{}.{}.{}",
-            self.header_b64()?,
-            self.payload_b64()?,
-            self.signature_b64()
-        ))
+        Ok(self.encode())
     }
 }
 
@@ -257,6 +494,7 @@ pub enum JwtError {
     MissingToken,
     InvalidToken,
     Expired(i64),
+    Revoked,
     InternalError(String),
 }
 
@@ -267,24 +505,33 @@ impl Display for JwtError {
 }
 
 // #[async_trait::async_trait]
-impl<S: Send + Sync> FromRequestParts<S> for Jwt {
+impl FromRequestParts<AppState> for Jwt {
     type Rejection = JwtError;
     
     fn from_request_parts(
         parts: &mut Parts,
-        _state: &S,
+        state: &AppState,
     ) -> impl Future<Output = Result<Self, Self::Rejection>> + Send {
         Box::pin(async move {
-            let TypedHeader(cookie) = parts
-                .extract::<TypedHeader<Cookie>>()
+            // Non-browser clients send `Authorization: Bearer`, browsers the `token` cookie.
+            let bearer = parts
+                .extract::<Option<TypedHeader<Authorization<Bearer>>>>()
+                .await
+                .map_err(|_| JwtError::InvalidToken)?;
+            let cookie = parts
+                .extract::<Option<TypedHeader<Cookie>>>()
                 .await
                 .map_err(|_| JwtError::InvalidToken)?;
-            let jwt_str = cookie.get("token").ok_or(JwtError::InvalidToken)?;
-            // let TypedHeader(Authorization(bearer)) = parts
-            //     .extract::<TypedHeader<Authorization<Bearer>>>().await
-            //     .map_err(|_| JwtError::InvalidToken)?;
-            // let jwt_str = bearer.token();
-            Jwt::parse_and_verify(jwt_str)
+            let jwt_str = match (&bearer, &cookie) {
+                (Some(TypedHeader(Authorization(bearer))), _) => bearer.token(),
+                (None, Some(TypedHeader(cookie))) => cookie.get("token").ok_or(JwtError::MissingToken)?,
+                (None, None) => return Err(JwtError::MissingToken),
+            };
+            let jwt = Jwt::parse_and_verify(jwt_str, &state.jwt_keys)?;
+            if state.revocations.is_revoked(&jwt) {
+                return Err(JwtError::Revoked);
+            }
+            Ok(jwt)
         })
     }
 }
@@ -298,6 +545,8 @@ impl IntoResponse for JwtError {
                 => (StatusCode::UNAUTHORIZED, "Invalid token"),
             JwtError::Expired(_)
                 => (StatusCode::UNAUTHORIZED, "Token expired"),
+            JwtError::Revoked
+                => (StatusCode::UNAUTHORIZED, "Token revoked"),
             JwtError::InternalError(_)
                 =>(StatusCode::INTERNAL_SERVER_ERROR, "Token Validation Error")
         };
@@ -310,16 +559,102 @@ impl IntoResponse for JwtError {
     }
 }
 
+#[cfg(test)]
+pub(crate) fn test_keys(keys: serde_json::Value) -> JwtKeys {
+    JwtKeys::from_config(serde_json::from_value(keys).unwrap()).unwrap()
+}
+
 #[test]
 fn jwt_test() {
-    let jwt = Jwt::new(114514, 60).unwrap();
-    let str = jwt.encode().unwrap();
+    let keys = test_keys(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }]
+    }));
+    let jwt = Jwt::new(114514, "test", 60, &keys).unwrap();
+    let str = jwt.encode();
     println!("{:?}", serde_json::to_string(&jwt).unwrap());
     println!("{}", str);
-    println!("\n");
     let jwt: Jwt = Jwt::try_from(str.as_str()).unwrap();
-    println!("{:?}", serde_json::to_string(&jwt).unwrap());
-    println!("{}", jwt.encode().unwrap());
+    assert_eq!(jwt.encode(), str);
+    assert_eq!(jwt.payload().exp - jwt.payload().iat, 60);
+    assert_eq!(Jwt::parse_and_verify(&str, &keys).unwrap().user_id(), 114514);
+
+    // claims are checked against the config, with leeway on the times
+    let other_aud = test_keys(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }],
+        "audience": "other",
+    }));
+    assert!(Jwt::parse_and_verify(&str, &other_aud).is_err());
+    let expired = Jwt::generate(114514, "test", -30, &keys).unwrap();
+    assert!(Jwt::parse_and_verify(&expired, &keys).is_ok());
+    let expired = Jwt::generate(114514, "test", -120, &keys).unwrap();
+    assert!(matches!(Jwt::parse_and_verify(&expired, &keys), Err(JwtError::Expired(_))));
+}
 
-    // let jwt = Jwt::from(r"eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJ1c2VyX2lkIjoxMTQ1MTQsImV4cF90aW1lIjoxNzE2ODAxMDY1NjU5fQ==.7D7kMJXmoomnEO8wzRXDQd2uAEsQNaVzJ2BKH_DCZNs=");
+#[test]
+fn jwt_asymmetric_test() {
+    let rng = SystemRandom::new();
+    let ed = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+    let es = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+    let keys = test_keys(json!({ "keys": [
+        { "kid": "hs", "secret": "test_secret_test_secret_test_secret" },
+        { "kid": "es", "alg": "ES256", "private_key": STANDARD.encode(es.as_ref()) },
+        { "kid": "ed", "alg": "EdDSA", "private_key": STANDARD.encode(ed.as_ref()) },
+    ]}));
+
+    let token = Jwt::generate(114514, "test", 60, &keys).unwrap();
+    let jwt = Jwt::parse_and_verify(&token, &keys).unwrap();
+    assert_eq!(jwt.header.alg, JwtAlg::EdDSA);
+
+    // only asymmetric keys are published
+    let jwks = keys.jwks();
+    let jwks = jwks["keys"].as_array().unwrap();
+    assert_eq!(jwks.len(), 2);
+    assert_eq!(jwks[0]["kid"], "es");
+    assert_eq!(jwks[0]["crv"], "P-256");
+    assert_eq!(jwks[1]["kty"], "OKP");
+
+    // a token can't pick a different algorithm than its key
+    let (_, payload_and_sig) = token.split_once('.').unwrap();
+    let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "HS256", "typ": "JWT", "kid": "ed" }).to_string());
+    assert!(Jwt::parse_and_verify(&format!("{header}.{payload_and_sig}"), &keys).is_err());
+
+    let es_only = test_keys(json!({ "keys": [
+        { "kid": "es", "alg": "ES256", "private_key": STANDARD.encode(es.as_ref()) },
+    ]}));
+    let token = Jwt::generate(114514, "test", 60, &es_only).unwrap();
+    assert!(Jwt::parse_and_verify(&token, &es_only).is_ok());
+}
+
+#[test]
+fn jwt_rotation_test() {
+    let now = Utc::now().timestamp();
+    let old = json!({ "kid": "old", "secret": "old_secret_old_secret_old_secret_old" });
+    let new = json!({ "kid": "new", "secret": "new_secret_new_secret_new_secret_new" });
+
+    let before = test_keys(json!({ "keys": [old] }));
+    let token = Jwt::generate(114514, "test", 60, &before).unwrap();
+
+    // `old` expired but is still within its grace period
+    let mut expired = old.clone();
+    expired["expire_at"] = json!(now - 10);
+    let after = test_keys(json!({ "keys": [expired.clone(), new.clone()] }));
+    assert!(Jwt::parse_and_verify(&token, &after).is_ok());
+    let fresh = Jwt::generate(114514, "test", 60, &after).unwrap();
+    assert_eq!(Jwt::try_from(fresh.as_str()).unwrap().header.kid, "new");
+
+    // grace period is over
+    let retired = test_keys(json!({ "keys": [expired, new.clone()], "grace_period": 5 }));
+    assert!(matches!(Jwt::parse_and_verify(&token, &retired), Err(JwtError::InvalidToken)));
+
+    // unknown kid and forged signatures
+    let other = test_keys(json!({ "keys": [new] }));
+    assert!(Jwt::parse_and_verify(&token, &other).is_err());
+    let forged = test_keys(json!({
+        "keys": [{ "kid": "old", "secret": "forged_secret_forged_secret_forged" }]
+    }));
+    assert!(Jwt::parse_and_verify(&token, &forged).is_err());
+
+    assert!(JwtKeys::from_config(serde_json::from_value(json!({
+        "keys": [{ "kid": "short", "secret": "test_secret" }]
+    })).unwrap()).is_err());
 }
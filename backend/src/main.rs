@@ -1,17 +1,23 @@
 mod email;
 mod entities;
 mod jwt;
+mod password;
+mod revocation;
 mod room;
 mod server;
+mod totp;
 mod uuid;
 mod sql;
 pub mod id;
 
 use anyhow::Result;
 use email::Email;
-use server::{fs_read, route};
+use jwt::{JwtConfig, JwtKeys};
+use server::{fs_read, route, AppState, LoneConfig};
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use chrono::Utc;
 use anyhow::anyhow;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -52,7 +58,41 @@ async fn main() -> Result<()> {
     let conn = conn.await
         .map_err(|e: anyhow::Error|anyhow!(format!("[Error] {}\tPlease check cfg/sql.json.", e)))?;
 
-    let app = route(conn);
+    let mailer = async {
+        let (config, template) = tokio::join!(
+            fs_read("./cfg/email.json"),
+            fs_read("../frontend/email.html")
+        );
+        Email::from(&config?, &template?)
+    };
+
+    let mailer = mailer.await
+        .map_err(|e: anyhow::Error|anyhow!(format!("[Error] {}\tPlease check cfg/email.json.", e)))?;
+
+    let jwt_keys = async {
+        let config: JwtConfig
+            = serde_json::from_str(&fs_read("./cfg/jwt.json").await?)?;
+        JwtKeys::from_config(config)
+    };
+
+    let jwt_keys = jwt_keys.await
+        .map_err(|e: anyhow::Error|anyhow!(format!("[Error] {}\tPlease check cfg/jwt.json.", e)))?;
+
+    // optional, the defaults apply without it
+    let lone_config = match fs_read("./cfg/lone.json").await {
+        Ok(config) => serde_json::from_str::<LoneConfig>(&config)
+            .map_err(|e| anyhow!(format!("[Error] {}\tPlease check cfg/lone.json.", e)))?,
+        Err(_) => LoneConfig::default(),
+    };
+
+    let state = AppState::new(conn, Arc::new(mailer), Arc::new(jwt_keys))
+        .with_lone_config(lone_config);
+    state.revocations.load().await
+        .map_err(|e| anyhow!(format!("[Error] Failed to load revoked tokens: {}", e)))?;
+    state.revocations.clone().spawn_pruner(Duration::from_secs(10 * 60));
+    state.auth_limiter.clone().spawn_pruner(Duration::from_secs(10 * 60));
+
+    let app = route(state);
     let listener = tokio::net::TcpListener::bind(addr).await?;
     println!("listening on {}", listener.local_addr()?);
     Ok(axum::serve(
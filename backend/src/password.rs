@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+// Current cost parameters, see OWASP password storage cheat sheet.
+// Hashes stored with other parameters are upgraded on the next successful login.
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Verified {
+    Invalid,
+    Valid,
+    /// Password matched, but the stored value is plaintext or uses outdated parameters.
+    NeedsRehash,
+}
+
+impl Verified {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Verified::Invalid)
+    }
+}
+
+fn hasher() -> Argon2<'static> {
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, None)
+        .expect("Invalid argon2 params");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_outdated(hash: &PasswordHash) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() {
+        return true;
+    }
+    if hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+    match Params::try_from(hash) {
+        Ok(params) => {
+            params.m_cost() != ARGON2_M_COST
+                || params.t_cost() != ARGON2_T_COST
+                || params.p_cost() != ARGON2_P_COST
+        }
+        Err(_) => true,
+    }
+}
+
+/// Hashes `password` into a PHC string with a fresh random salt.
+pub fn hash_blocking(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = hasher()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+/// Verifies `password` against `stored`, which is either a PHC string or a legacy plaintext row.
+pub fn verify_blocking(password: &str, stored: &str) -> Verified {
+    let hash = match PasswordHash::new(stored) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return if constant_time_eq(password.as_bytes(), stored.as_bytes()) {
+                Verified::NeedsRehash
+            } else {
+                Verified::Invalid
+            };
+        }
+    };
+
+    if hasher().verify_password(password.as_bytes(), &hash).is_err() {
+        return Verified::Invalid;
+    }
+    if is_outdated(&hash) {
+        Verified::NeedsRehash
+    } else {
+        Verified::Valid
+    }
+}
+
+pub async fn hash(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || hash_blocking(&password)).await?
+}
+
+pub async fn verify(password: String, stored: String) -> Verified {
+    tokio::task::spawn_blocking(move || verify_blocking(&password, &stored))
+        .await
+        .unwrap_or(Verified::Invalid)
+}
+
+
+#[test]
+fn password_test() {
+    let hash = hash_blocking("114514").unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert_ne!(hash, hash_blocking("114514").unwrap());
+
+    assert_eq!(verify_blocking("114514", &hash), Verified::Valid);
+    assert_eq!(verify_blocking("1919810", &hash), Verified::Invalid);
+
+    // legacy plaintext rows
+    assert_eq!(verify_blocking("114514", "114514"), Verified::NeedsRehash);
+    assert_eq!(verify_blocking("1919810", "114514"), Verified::Invalid);
+
+    // outdated cost params
+    let params = Params::new(8 * 1024, 1, 1, None).unwrap();
+    let salt = SaltString::generate(&mut OsRng);
+    let old = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(b"114514", &salt)
+        .unwrap()
+        .to_string();
+    assert_eq!(verify_blocking("114514", &old), Verified::NeedsRehash);
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use sea_orm::DatabaseConnection;
+
+use crate::id::{GeneralId, UserId};
+use crate::jwt::Jwt;
+use crate::sql::{DataBase, token_revocation};
+
+fn to_naive(timestamp_ms: i64) -> NaiveDateTime {
+    DateTime::from_timestamp_millis(timestamp_ms)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+/// Access tokens revoked before their `exp_time`.
+///
+/// Postgres is the source of truth, every check is answered from the in-memory copy.
+/// Rows are kept only until the tokens they cover would have expired anyway.
+#[derive(Debug)]
+pub struct RevocationList {
+    db:         token_revocation::DB,
+    /// jti -> expire time (ms)
+    tokens:     DashMap<String, i64>,
+    /// session id -> time (ms) after which no token of the session is left
+    sessions:   DashMap<String, i64>,
+    /// user pk -> tokens issued before this time (s) are revoked, `iat` has second precision
+    users:      DashMap<u32, i64>,
+    /// lifetime (s) of access tokens, bounds how long a user-wide revocation is kept
+    token_ttl:  i64,
+}
+
+impl RevocationList {
+    pub fn new(db_conn: DatabaseConnection, token_ttl: i64) -> Self {
+        Self {
+            db:         token_revocation::DB::from_conn(db_conn),
+            tokens:     DashMap::new(),
+            sessions:   DashMap::new(),
+            users:      DashMap::new(),
+            token_ttl,
+        }
+    }
+
+    /// Fills the cache with every revocation that hasn't expired yet.
+    pub async fn load(&self) -> Result<()> {
+        let now = Utc::now();
+        for model in self.db.select_active(now.naive_utc()).await? {
+            let expire_at = model.expire_at.and_utc().timestamp_millis();
+            match (model.jti, model.session_id) {
+                (Some(jti), _) => { self.tokens.insert(jti, expire_at); },
+                (None, Some(sid)) => { self.sessions.insert(sid, expire_at); },
+                (None, None) => self.cache_user(model.user_id as u32, model.revoked_at.and_utc().timestamp()),
+            }
+        }
+        Ok(())
+    }
+
+    fn cache_user(&self, user_pk: u32, revoked_at: i64) {
+        self.users.entry(user_pk)
+            .and_modify(|t| *t = (*t).max(revoked_at))
+            .or_insert(revoked_at);
+    }
+
+    pub fn is_revoked(&self, jwt: &Jwt) -> bool {
+        if self.tokens.contains_key(jwt.jti()) || self.sessions.contains_key(jwt.session_id()) {
+            return true;
+        }
+        // A token minted in the same second as the revocation is kept, so logging in again
+        // right after revoking everything works. Tokens to cut off for sure are revoked by
+        // jti or session.
+        let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode();
+        self.users.get(&user_pk).is_some_and(|t| jwt.issued_at() < *t)
+    }
+
+    pub async fn revoke(&self, jwt: &Jwt) -> Result<()> {
+        let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode();
+        let expire_at = jwt.expire_time() * 1000;
+        self.db.revoke_jti(user_pk as i32, jwt.jti(), to_naive(expire_at)).await?;
+        self.tokens.insert(jwt.jti().to_string(), expire_at);
+        Ok(())
+    }
+
+    /// Revokes every access token minted for a session.
+    pub async fn revoke_session(&self, user_pk: u32, session_id: &str) -> Result<()> {
+        let expire_at = Utc::now().timestamp_millis() + self.token_ttl * 1000;
+        self.db.revoke_session(user_pk as i32, session_id, to_naive(expire_at)).await?;
+        self.sessions.insert(session_id.to_string(), expire_at);
+        Ok(())
+    }
+
+    /// Revokes every access token `user_pk` was issued before the current second.
+    pub async fn revoke_user(&self, user_pk: u32) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let expire_at = (now + self.token_ttl) * 1000;
+        self.db.revoke_user(user_pk as i32, to_naive(now * 1000), to_naive(expire_at)).await?;
+        self.cache_user(user_pk, now);
+        Ok(())
+    }
+
+    /// Drops entries whose tokens have expired on their own.
+    pub async fn prune(&self) -> Result<u64> {
+        let now = Utc::now().timestamp_millis();
+        let pruned = self.db.delete_expired(to_naive(now)).await?;
+        self.tokens.retain(|_, expire_at| *expire_at > now);
+        self.sessions.retain(|_, expire_at| *expire_at > now);
+        self.users.retain(|_, revoked_at| (*revoked_at + self.token_ttl) * 1000 > now);
+        Ok(pruned)
+    }
+
+    pub fn spawn_pruner(self: Arc<Self>, period: Duration) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(period).await;
+                if let Err(e) = self.prune().await {
+                    println!("[Revocation] Failed to prune: {}", e);
+                }
+            }
+        });
+    }
+}
+
+
+#[test]
+fn revocation_test() {
+    use crate::jwt::test_keys;
+    use serde_json::json;
+
+    let keys = test_keys(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }]
+    }));
+    let parse = |s: String| Jwt::parse_and_verify(&s, &keys).unwrap();
+    let uid = UserId::from_decoded(114514u32);
+    let jwt1 = parse(Jwt::generate(uid.encode() as usize, "test", 60, &keys).unwrap());
+    let jwt2 = parse(Jwt::generate(uid.encode() as usize, "test", 60, &keys).unwrap());
+
+    let list = RevocationList::new(Default::default(), 60);
+    assert!(!list.is_revoked(&jwt1));
+    list.tokens.insert(jwt1.jti().to_string(), jwt1.expire_time() * 1000);
+    assert!(list.is_revoked(&jwt1));
+    assert!(!list.is_revoked(&jwt2));
+
+    let other = parse(Jwt::generate(uid.encode() as usize, "other", 60, &keys).unwrap());
+    list.sessions.insert("other".to_string(), other.expire_time() * 1000);
+    assert!(list.is_revoked(&other));
+    assert!(!list.is_revoked(&jwt2));
+
+    // a token from the second of the revocation survives it, an older one doesn't
+    list.cache_user(114514, jwt2.issued_at());
+    assert!(!list.is_revoked(&jwt2));
+    list.cache_user(114514, jwt2.issued_at() + 1);
+    assert!(list.is_revoked(&jwt2));
+}
@@ -0,0 +1,252 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::{notify_lone, notify_users, room_viewers, Event};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    category,
+    lone,
+};
+use crate::id::{CategoryId, GeneralId, LoneId, RoomId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::{member_lone, privileged_lone};
+use super::room::{lone_room, room_json};
+
+const CATEGORY_NAME_MAX_LEN: usize = 32;
+
+#[derive(Debug, Deserialize)]
+struct CategoryParams {
+    name:       Option<String>,
+}
+
+impl CategoryParams {
+    fn name(&self) -> Option<String> {
+        let name = self.name.as_ref()?.trim();
+        let len = name.chars().count();
+        (len > 0 && len <= CATEGORY_NAME_MAX_LEN).then(|| name.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReorderParams {
+    category_ids:   Vec<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MoveParams {
+    /// none takes the room out of its category
+    category_id:    Option<u32>,
+    position:       u32,
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones/{id}/categories", get(get_categories).post(post_category).put(put_categories))
+        .route("/lones/{id}/categories/{category_id}", patch(patch_category).delete(delete_category))
+        .route("/lones/{id}/rooms/{room_id}/move", post(move_room))
+        .with_state(app_state)
+}
+
+fn category_json(model: &category::Model) -> Value {
+    json!({
+        "id":           CategoryId::from_decoded(model.id as u32).encode(),
+        "lone_id":      LoneId::from_decoded(model.lone_id as u32).encode(),
+        "name":         model.name,
+        "position":     model.position,
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+fn db_err(tag: &str, e: anyhow::Error) -> ServerResponse {
+    println!("[Category({})] Error: {}", tag, e);
+    ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+}
+
+fn announce(state: &AppState, lone_id: i32, event: Event) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        notify_lone(&state, lone_id, None, event).await;
+    });
+}
+
+async fn lone_category(
+    state: &AppState, lone: &lone::Model, category_id: u32
+) -> Result<category::Model, ServerResponse> {
+    let category_pk = CategoryId::from_encoded(category_id).decode() as i32;
+    match category::DB::from_state(state).select_pk(category_pk).await {
+        Ok(Some(model)) if model.lone_id == lone.id => Ok(model),
+        Ok(_) => Err(ServerResponse::fine(ServerResponseError::InvalidCategoryId, None)),
+        Err(e) => Err(db_err("get", e.into())),
+    }
+}
+
+async fn get_categories(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match category::DB::from_state(&state).select_lone(lone.id).await {
+        Ok(models) => {
+            let categories = models.iter().map(category_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "categories": categories })))
+        },
+        Err(e) => db_err("get", e),
+    }
+}
+
+async fn post_category(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<CategoryParams>,
+) -> impl IntoResponse {
+    let Some(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidCategoryParams, None);
+    };
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match category::DB::from_state(&state).create(lone.id, name).await {
+        Ok(model) => {
+            announce(&state, lone.id, Event::CategoryCreate { category: category_json(&model) });
+            ServerResponse::ok(Some(category_json(&model)))
+        },
+        Err(e) => db_err("post", e),
+    }
+}
+
+async fn patch_category(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, category_id)): Path<(u32, u32)>,
+    Json(params): Json<CategoryParams>,
+) -> impl IntoResponse {
+    let Some(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidCategoryParams, None);
+    };
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let model = match lone_category(&state, &lone, category_id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    match category::DB::from_state(&state).rename(model.id, name).await {
+        Ok(model) => {
+            announce(&state, lone.id, Event::CategoryUpdate { category: category_json(&model) });
+            ServerResponse::ok(Some(category_json(&model)))
+        },
+        Err(e) => db_err("patch", e),
+    }
+}
+
+/// Deletes a category, its rooms are kept without one.
+async fn delete_category(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, category_id)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let model = match lone_category(&state, &lone, category_id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    match category::DB::from_state(&state).remove(lone.id, model.id).await {
+        Ok(()) => {
+            // its rooms lost the category overrides
+            state.permissions.invalidate_lone(lone.id);
+            announce(&state, lone.id, Event::CategoryDelete { category_id });
+            ServerResponse::ok(None)
+        },
+        Err(e) => db_err("delete", e),
+    }
+}
+
+/// Reorders every category of the lone at once.
+async fn put_categories(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<ReorderParams>,
+) -> impl IntoResponse {
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let category_ids = params.category_ids.iter()
+        .map(|id| CategoryId::from_encoded(*id).decode() as i32)
+        .collect::<Vec<_>>();
+    match category::DB::from_state(&state).reorder(lone.id, &category_ids).await {
+        Ok(Some(models)) => {
+            announce(&state, lone.id, Event::CategoryReorder { category_ids: params.category_ids });
+            let categories = models.iter().map(category_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "categories": categories })))
+        },
+        Ok(None) => ServerResponse::fine(ServerResponseError::InvalidCategoryParams, None),
+        Err(e) => db_err("put", e),
+    }
+}
+
+/// Moves a room into a category, or out of it, at a position among its rooms.
+async fn move_room(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, room_id)): Path<(u32, u32)>,
+    Json(params): Json<MoveParams>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let room = match lone_room(&state, &jwt, &lone, room_id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    let category_pk = params.category_id.map(|id| CategoryId::from_encoded(id).decode() as i32);
+    let viewers = room_viewers(&state, &lone, room.id).await;
+    let moved = category::DB::from_state(&state)
+        .move_room(lone.id, room.id, category_pk, params.position as usize).await;
+    match moved {
+        Ok(Some(model)) => {
+            // the room now inherits from another category
+            state.permissions.invalidate_lone(lone.id);
+            let event = Event::RoomMove {
+                room_id:        RoomId::from_decoded(model.id as u32).encode(),
+                category_id:    params.category_id,
+                position:       model.position,
+            };
+            // those who could see the room before the move or can now
+            tokio::spawn(async move {
+                let mut users = room_viewers(&state, &lone, model.id).await;
+                for user_id in viewers {
+                    if !users.contains(&user_id) {
+                        users.push(user_id);
+                    }
+                }
+                notify_users(&state, lone.id, &users, event).await;
+            });
+            ServerResponse::ok(Some(room_json(&model)))
+        },
+        Ok(None) => ServerResponse::fine(ServerResponseError::InvalidCategoryId, None),
+        Err(e) => db_err("move", e),
+    }
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use nanoid::nanoid;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::Event;
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    lone,
+    lone_invite,
+};
+use crate::id::{GeneralId, LoneId, RoomId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::{lone_json, member_lone, user_pk};
+use super::member::announce;
+use super::room::lone_room;
+
+const INVITE_CODE_LEN: usize = 10;
+const MAX_INVITE_USES: u32 = 1000;
+const MAX_INVITE_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Deserialize)]
+struct InviteParams {
+    max_uses:   Option<u32>,
+    /// seconds until the invite expires, never if absent
+    expires_in: Option<u64>,
+    room_id:    Option<u32>,
+}
+
+impl InviteParams {
+    fn is_valid(&self) -> bool {
+        self.max_uses.is_none_or(|uses| (1..=MAX_INVITE_USES).contains(&uses))
+            && self.expires_in.is_none_or(|secs| (1..=MAX_INVITE_AGE.as_secs()).contains(&secs))
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones/{id}/invites", get(get_invites).post(post_invite))
+        .route("/lones/{id}/invites/{code}", delete(delete_invite))
+        .route("/invites/{code}", post(post_redeem))
+        .with_state(app_state)
+}
+
+fn invite_json(model: &lone_invite::Model) -> Value {
+    json!({
+        "code":         model.code,
+        "lone_id":      LoneId::from_decoded(model.lone_id as u32).encode(),
+        "creator_id":   UserId::from_decoded(model.creator_id as u32).encode(),
+        "room_id":      model.room_id.map(|id| RoomId::from_decoded(id as u32).encode()),
+        "max_uses":     model.max_uses,
+        "uses":         model.uses,
+        "expire_at":    model.expire_at.map(|time| time.and_utc().timestamp_millis()),
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+/// Any member may invite people, into one of the lone's rooms if they like.
+async fn post_invite(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<InviteParams>,
+) -> impl IntoResponse {
+    if !params.is_valid() {
+        return ServerResponse::fine(ServerResponseError::InvalidInviteParams, None);
+    }
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+
+    // only into rooms the inviter can see themselves
+    let room_id = match params.room_id {
+        None => None,
+        Some(room_id) => match lone_room(&state, &jwt, &lone, room_id, RolePrivilege::VIEW_ROOM).await {
+            Ok(room) => Some(room.id),
+            Err(res) => return res,
+        },
+    };
+    let expire_at = params.expires_in
+        .map(|secs| Utc::now().naive_utc() + Duration::from_secs(secs));
+
+    let res = lone_invite::DB::from_state(&state).create(
+        nanoid!(INVITE_CODE_LEN),
+        lone.id,
+        user_pk(&jwt),
+        room_id,
+        params.max_uses.map(|uses| uses as i32),
+        expire_at,
+    ).await;
+    match res {
+        Ok(model) => ServerResponse::ok(Some(invite_json(&model))),
+        Err(e) => {
+            println!("[Invite(post)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// The owner sees every invite of the lone, other members only their own.
+async fn get_invites(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let user_pk = user_pk(&jwt);
+    match lone_invite::DB::from_state(&state).select_lone(lone.id).await {
+        Ok(models) => {
+            let invites = models.iter()
+                .filter(|invite| lone.owner_id == user_pk || invite.creator_id == user_pk)
+                .map(invite_json)
+                .collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "invites": invites })))
+        },
+        Err(e) => {
+            println!("[Invite(get)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Revokes an invite, allowed for its creator and the owner.
+async fn delete_invite(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, code)): Path<(u32, String)>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let db = lone_invite::DB::from_state(&state);
+    let invite = match db.select_pk(code).await {
+        Ok(Some(invite)) if invite.lone_id == lone.id => invite,
+        Ok(_) => return ServerResponse::fine(ServerResponseError::InvalidInviteCode, None),
+        Err(e) => {
+            println!("[Invite(delete)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+    let user_pk = user_pk(&jwt);
+    if invite.creator_id != user_pk && lone.owner_id != user_pk {
+        return ServerResponse::fine(ServerResponseError::DeniedLonePermission, None);
+    }
+    match db.delete_pk(invite.code).await {
+        Ok(_) => ServerResponse::ok(None),
+        Err(e) => {
+            println!("[Invite(delete)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Joins the lone behind the invite and lets connected members know.
+async fn post_redeem(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> impl IntoResponse {
+    let user_pk = user_pk(&jwt);
+    let invite = match lone_invite::DB::from_state(&state).redeem(&code, user_pk).await {
+        Ok(lone_invite::Redeem::Joined(invite)) => {
+            state.permissions.invalidate_member(invite.lone_id, user_pk);
+            let event = Event::MemberJoin { user_id: UserId::from_decoded(user_pk as u32).encode() };
+            announce(&state, invite.lone_id, user_pk, event);
+            invite
+        },
+        Ok(lone_invite::Redeem::AlreadyMember(invite)) => invite,
+        Ok(lone_invite::Redeem::Banned) => return ServerResponse::fine(ServerResponseError::BannedFromLone, None),
+        Ok(lone_invite::Redeem::NotFound) => return ServerResponse::fine(ServerResponseError::InvalidInviteCode, None),
+        Ok(lone_invite::Redeem::Expired) => return ServerResponse::fine(ServerResponseError::ExpiredInviteCode, None),
+        Err(e) => {
+            println!("[Invite(redeem)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+
+    match lone::DB::from_state(&state).select_pk(invite.lone_id).await {
+        Ok(Some(model)) => ServerResponse::ok(Some(json!({
+            "lone":     lone_json(&model),
+            "room_id":  invite.room_id.map(|id| RoomId::from_decoded(id as u32).encode()),
+        }))),
+        Ok(None) => ServerResponse::fine(ServerResponseError::InvalidInviteCode, None),
+        Err(e) => {
+            println!("[Invite(redeem)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+
+#[test]
+fn invite_usable_test() {
+    let now = Utc::now().naive_utc();
+    let invite = lone_invite::Model {
+        code:       nanoid!(INVITE_CODE_LEN),
+        lone_id:    1,
+        creator_id: 1,
+        room_id:    None,
+        max_uses:   Some(2),
+        uses:       1,
+        expire_at:  Some(now + Duration::from_secs(60)),
+        created_at: now,
+    };
+    assert!(lone_invite::is_usable(&invite, now));
+    assert!(!lone_invite::is_usable(&lone_invite::Model { uses: 2, ..invite.clone() }, now));
+    assert!(!lone_invite::is_usable(&invite, now + Duration::from_secs(61)));
+    let forever = lone_invite::Model { max_uses: None, expire_at: None, uses: 1000, ..invite };
+    assert!(lone_invite::is_usable(&forever, now));
+
+    let params = |max_uses, expires_in| InviteParams { max_uses, expires_in, room_id: None };
+    assert!(params(None, None).is_valid());
+    assert!(params(Some(1), Some(3600)).is_valid());
+    assert!(!params(Some(0), None).is_valid());
+    assert!(!params(None, Some(MAX_INVITE_AGE.as_secs() + 1)).is_valid());
+}
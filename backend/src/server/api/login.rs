@@ -1,20 +1,29 @@
+use std::net::SocketAddr;
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     http::StatusCode,
     response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use axum_extra::extract::cookie;
+use axum_extra::{headers::UserAgent, TypedHeader};
+use nanoid::nanoid;
 use serde::Deserialize;
+use serde_json::json;
 
 use crate::server::{
-    fs_read, is_valid_email, AppState, ServerResponse, ServerResponseError, JWT_EXPIRE_DURATION,
+    fs_read, is_valid_email, AppState, ServerResponse, ServerResponseError,
 };
+use crate::server::rate_limit::RateLimitLayer;
 
 use crate::jwt::Jwt;
+use crate::password;
+use crate::server::api::{token, two_factor};
+use crate::server::websocket::ws;
 use crate::sql::{
     DataBase,
+    refresh_token,
+    session,
     user,
 };
 use crate::id::{GeneralId, UserId};
@@ -28,7 +37,9 @@ struct LoginParams {
 pub(crate) fn route(app_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/login", get(get_login))
-        .route("/login", post(post_login))
+        .route("/login", post(post_login).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .route("/logout", post(post_logout))
+        .route("/logout/all", post(post_logout_all))
         .with_state(app_state)
 }
 
@@ -38,6 +49,8 @@ async fn get_login() -> Html<String> {
 
 async fn post_login(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(params): Json<LoginParams>
 ) -> impl IntoResponse {
     println!("post(login) called with params: {:?}", params);
@@ -58,34 +71,110 @@ async fn post_login(
     let user_model = match db.select_email(&email).await {
         Ok(Some(user_model)) => user_model,
         Ok(None) => {
+            state.auth_limiter.record_failure(Some(addr.ip()), &email);
             return ServerResponse::fine(ServerResponseError::InvalidLoginParams, None);
         },
         Err(_) => {
             return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
         },
     };
-    if user_model.password.eq(&password) {
-        let user_id = UserId::from_decoded(user_model.id as u32);
+    let verified = password::verify(password.clone(), user_model.password.clone()).await;
+    if verified == password::Verified::NeedsRehash {
+        // Plaintext rows and hashes with outdated cost params are upgraded in place.
+        match password::hash(password).await {
+            Ok(hash) => {
+                if let Err(e) = db.update_password(user_model.id, hash).await {
+                    println!("[Login(post)] Failed to rehash password: {}", e);
+                }
+            }
+            Err(e) => println!("[Login(post)] Failed to rehash password: {}", e),
+        }
+    }
+    if verified.is_valid() {
         println!("post(login) user found");
-        let jwt = Jwt::generate(user_id.encode() as usize, JWT_EXPIRE_DURATION);
-        if let Ok(jwt) = jwt {
-            let jwt_cookie = cookie::Cookie::build(("token", jwt))
-                .path("/")
-                .max_age(time::Duration::seconds(JWT_EXPIRE_DURATION))
-                .http_only(true)
-                .secure(false)
-                .build();
-
-            return ServerResponse::ok(None)
-                .set_cookie(jwt_cookie)
-                .unwrap_or_else(|_e| {
-                    ServerResponse::inner_err(ServerResponseError::InternalTokenGenError)
-                });
-        };
+        state.auth_limiter.record_success(Some(addr.ip()), &email);
+        let user_agent = user_agent.map(|TypedHeader(ua)| ua.to_string());
+        match two_factor::is_enabled(&state, user_model.id).await {
+            Ok(true) => {
+                // No cookie yet, the ticket has to be exchanged at `/login/2fa` first.
+                let ticket = state.two_factor_logins
+                    .start(user_model.id, addr.ip().to_string(), user_agent);
+                ServerResponse::fine(
+                    ServerResponseError::RequiredTwoFactor,
+                    Some(json!({ "ticket": ticket })),
+                )
+            },
+            Ok(false) => open_session(&state, user_model.id, addr.ip().to_string(), user_agent).await,
+            Err(e) => {
+                println!("[Login(post)] Error: {}", e);
+                ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+            }
+        }
     } else {
         println!("post(login) user not found");
-        return ServerResponse::fine(ServerResponseError::InvalidLoginParams, None);
+        state.auth_limiter.record_failure(Some(addr.ip()), &email);
+        ServerResponse::fine(ServerResponseError::InvalidLoginParams, None)
+    }
+}
+
+/// Records a new session for a fully authenticated login and sets its token cookies.
+pub(crate) async fn open_session(
+    state: &AppState,
+    user_pk: i32,
+    ip: String,
+    user_agent: Option<String>,
+) -> ServerResponse {
+    let session_id = nanoid!();
+    let created = session::DB::from_state(state)
+        .create(&session_id, user_pk, ip, user_agent)
+        .await;
+    if let Err(e) = created {
+        println!("[Login(post)] Failed to create session: {}", e);
+        return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+    }
+    token::grant(state, user_pk, &session_id)
+        .await
+        .unwrap_or_else(ServerResponse::inner_err)
+}
+
+async fn post_logout(
+    jwt: Jwt,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    // The refresh family shares the session id, revoking it keeps the session from
+    // minting new access tokens even when the client sent no refresh cookie.
+    let revoked = async {
+        state.revocations.revoke(&jwt).await?;
+        session::DB::from_state(&state).revoke(jwt.session_id()).await?;
+        refresh_token::DB::from_state(&state).revoke_family(jwt.session_id()).await?;
+        anyhow::Ok(())
+    };
+    if let Err(e) = revoked.await {
+        println!("[Logout(post)] Error: {}", e);
+        return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+    }
+
+    token::clear_cookies(ServerResponse::ok(None))
+}
+
+/// Signs `user_pk` out everywhere: access and refresh tokens, sessions and the open websocket.
+pub(crate) async fn revoke_all(state: &AppState, user_pk: u32) -> anyhow::Result<()> {
+    state.revocations.revoke_user(user_pk).await?;
+    refresh_token::DB::from_state(state).revoke_user(user_pk as i32).await?;
+    session::DB::from_state(state).revoke_user(user_pk as i32).await?;
+    ws::disconnect(&state.users, user_pk, None).await;
+    Ok(())
+}
+
+async fn post_logout_all(
+    jwt: Jwt,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode();
+    if let Err(e) = revoke_all(&state, user_pk).await {
+        println!("[Logout(post)] Error: {}", e);
+        return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
     }
 
-    ServerResponse::inner_err(ServerResponseError::InternalUnknownError)
+    token::clear_cookies(ServerResponse::ok(None))
 }
@@ -0,0 +1,262 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    lone,
+};
+use crate::id::{GeneralId, LoneId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+
+const LONE_NAME_MAX_LEN: usize = 32;
+const MAX_OWNED_LONES: u64 = 10;
+
+/// Read from `cfg/lone.json`, every field is optional.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct LoneConfig {
+    /// How many lones a single user may own at once.
+    #[serde(default = "default_max_owned")]
+    pub max_owned: u64,
+}
+
+fn default_max_owned() -> u64 {
+    MAX_OWNED_LONES
+}
+
+impl Default for LoneConfig {
+    fn default() -> Self {
+        Self { max_owned: MAX_OWNED_LONES }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LoneParams {
+    name:       Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransferParams {
+    user_id:    u32,
+}
+
+impl LoneParams {
+    /// The trimmed name, if it fits the `lone_info.name` column.
+    fn name(&self) -> Option<String> {
+        let name = self.name.as_ref()?.trim();
+        let len = name.chars().count();
+        (len > 0 && len <= LONE_NAME_MAX_LEN).then(|| name.to_string())
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones", get(get_lones).post(post_lone))
+        .route("/lones/{id}", get(get_lone).patch(patch_lone).delete(delete_lone))
+        .route("/lones/{id}/transfer", post(post_transfer))
+        .with_state(app_state)
+}
+
+pub(crate) fn lone_json(model: &lone::Model) -> Value {
+    json!({
+        "id":           LoneId::from_decoded(model.id as u32).encode(),
+        "name":         model.name,
+        "owner_id":     UserId::from_decoded(model.owner_id as u32).encode(),
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+pub(crate) fn user_pk(jwt: &Jwt) -> i32 {
+    UserId::from_encoded(jwt.user_id() as u32).decode() as i32
+}
+
+/// Loads a lone the caller belongs to. Lones they aren't in look the same as missing ones.
+pub(crate) async fn member_lone(state: &AppState, jwt: &Jwt, id: u32) -> Result<lone::Model, ServerResponse> {
+    let db = lone::DB::from_state(state);
+    let lone_pk = LoneId::from_encoded(id).decode() as i32;
+    let found = async {
+        let Some(model) = db.select_pk(lone_pk).await? else {
+            return anyhow::Ok(None);
+        };
+        Ok(db.is_member(lone_pk, user_pk(jwt)).await?.then_some(model))
+    };
+    match found.await {
+        Ok(Some(model)) => Ok(model),
+        Ok(None) => Err(ServerResponse::fine(ServerResponseError::InvalidLoneId, None)),
+        Err(e) => {
+            println!("[Lone] Error: {}", e);
+            Err(ServerResponse::inner_err(ServerResponseError::InternalDatabaseError))
+        }
+    }
+}
+
+/// Like [`member_lone`], but only the owner gets through.
+pub(crate) async fn owned_lone(state: &AppState, jwt: &Jwt, id: u32) -> Result<lone::Model, ServerResponse> {
+    let model = member_lone(state, jwt, id).await?;
+    if model.owner_id != user_pk(jwt) {
+        return Err(ServerResponse::fine(ServerResponseError::DeniedLonePermission, None));
+    }
+    Ok(model)
+}
+
+/// Like [`member_lone`], but the caller needs `privilege` across the lone.
+pub(crate) async fn privileged_lone(
+    state: &AppState, jwt: &Jwt, id: u32, privilege: RolePrivilege
+) -> Result<lone::Model, ServerResponse> {
+    let model = member_lone(state, jwt, id).await?;
+    match state.permissions.in_lone(&state.db_conn, &model, user_pk(jwt)).await {
+        Ok(held) if held.allows(privilege) => Ok(model),
+        Ok(_) => Err(ServerResponse::fine(ServerResponseError::DeniedLonePermission, None)),
+        Err(e) => {
+            println!("[Lone] Error: {}", e);
+            Err(ServerResponse::inner_err(ServerResponseError::InternalDatabaseError))
+        }
+    }
+}
+
+async fn post_lone(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Json(params): Json<LoneParams>,
+) -> impl IntoResponse {
+    let Some(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidLoneParams, None);
+    };
+    let max_owned = state.lone_config.max_owned;
+    match lone::DB::from_state(&state).create(user_pk(&jwt), name, max_owned).await {
+        Ok(Some(model)) => ServerResponse::ok(Some(lone_json(&model))),
+        Ok(None) => ServerResponse::fine(ServerResponseError::ExceededLoneLimit, Some(json!({ "max_owned": max_owned }))),
+        Err(e) => {
+            println!("[Lone(post)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+async fn get_lones(
+    jwt: Jwt,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match lone::DB::from_state(&state).select_member(user_pk(&jwt)).await {
+        Ok(models) => {
+            let lones = models.iter().map(lone_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "lones": lones })))
+        },
+        Err(e) => {
+            println!("[Lone(get)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+async fn get_lone(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    match member_lone(&state, &jwt, id).await {
+        Ok(model) => ServerResponse::ok(Some(lone_json(&model))),
+        Err(res) => res,
+    }
+}
+
+async fn patch_lone(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<LoneParams>,
+) -> impl IntoResponse {
+    let Some(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidLoneParams, None);
+    };
+    let model = match owned_lone(&state, &jwt, id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    match lone::DB::from_state(&state).rename(model.id, name).await {
+        Ok(model) => ServerResponse::ok(Some(lone_json(&model))),
+        Err(e) => {
+            println!("[Lone(patch)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Hands the lone to another member, the previous owner stays a member.
+async fn post_transfer(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<TransferParams>,
+) -> impl IntoResponse {
+    let to = UserId::from_encoded(params.user_id).decode() as i32;
+    if to == user_pk(&jwt) {
+        return ServerResponse::fine(ServerResponseError::InvalidLoneParams, None);
+    }
+    let model = match owned_lone(&state, &jwt, id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    let max_owned = state.lone_config.max_owned;
+    match lone::DB::from_state(&state).transfer(model.id, model.owner_id, to, max_owned).await {
+        Ok(lone::Transfer::Done(model)) => {
+            state.permissions.invalidate_lone(model.id);
+            ServerResponse::ok(Some(lone_json(&model)))
+        },
+        // ownership changed between the check above and the transaction
+        Ok(lone::Transfer::NotOwner) => ServerResponse::fine(ServerResponseError::DeniedLonePermission, None),
+        Ok(lone::Transfer::NotMember) => ServerResponse::fine(ServerResponseError::InvalidLoneMember, None),
+        Ok(lone::Transfer::LimitReached) => ServerResponse::fine(ServerResponseError::ExceededLoneLimit, Some(json!({ "max_owned": max_owned }))),
+        Err(e) => {
+            println!("[Lone(transfer)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Roles, memberships and rooms go with the lone through `ON DELETE CASCADE`.
+async fn delete_lone(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let model = match owned_lone(&state, &jwt, id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    match lone::DB::from_state(&state).delete_pk(model.id).await {
+        Ok(_) => {
+            state.permissions.invalidate_lone(model.id);
+            ServerResponse::ok(None)
+        },
+        Err(e) => {
+            println!("[Lone(delete)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+
+#[test]
+fn lone_params_test() {
+    let params = |name: &str| LoneParams { name: Some(name.to_string()) };
+    assert_eq!(params("  ChatAlone ").name(), Some("ChatAlone".to_string()));
+    assert_eq!(params("   ").name(), None);
+    assert_eq!(params(&"孤".repeat(32)).name(), Some("孤".repeat(32)));
+    assert_eq!(params(&"a".repeat(33)).name(), None);
+    assert_eq!(LoneParams { name: None }.name(), None);
+
+    let config: LoneConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(config.max_owned, MAX_OWNED_LONES);
+    let config: LoneConfig = serde_json::from_str(r#"{"max_owned": 3}"#).unwrap();
+    assert_eq!(config.max_owned, 3);
+}
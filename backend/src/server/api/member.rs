@@ -0,0 +1,281 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::{notify_lone, Event};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    DataBase,
+    lone,
+    lone_ban,
+    lone_user,
+};
+use crate::id::{GeneralId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::{member_lone, privileged_lone, user_pk};
+use super::role::rank;
+
+const BAN_REASON_MAX_LEN: usize = 256;
+const MAX_BAN_DURATION: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+#[derive(Debug, Deserialize)]
+struct BanParams {
+    reason:     Option<String>,
+    /// seconds until the ban is lifted, permanent if absent
+    expires_in: Option<u64>,
+}
+
+impl BanParams {
+    fn is_valid(&self) -> bool {
+        self.reason.as_ref().is_none_or(|reason| reason.chars().count() <= BAN_REASON_MAX_LEN)
+            && self.expires_in.is_none_or(|secs| (1..=MAX_BAN_DURATION.as_secs()).contains(&secs))
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones/{id}/members", get(get_members))
+        .route("/lones/{id}/members/{user_id}", delete(delete_member))
+        .route("/lones/{id}/leave", post(post_leave))
+        .route("/lones/{id}/bans", get(get_bans))
+        .route("/lones/{id}/bans/{user_id}", put(put_ban).delete(delete_ban))
+        .with_state(app_state)
+}
+
+fn encode_user(user_pk: i32) -> u32 {
+    UserId::from_decoded(user_pk as u32).encode()
+}
+
+fn ban_json(model: &lone_ban::Model) -> Value {
+    json!({
+        "user_id":      encode_user(model.user_id),
+        "moderator_id": model.moderator_id.map(encode_user),
+        "reason":       model.reason,
+        "expire_at":    model.expire_at.map(|time| time.and_utc().timestamp_millis()),
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+/// Sends a membership event to the lone, and to `user_pk` who may not be in it anymore.
+pub(crate) fn announce(state: &AppState, lone_id: i32, user_pk: i32, event: Event) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        notify_lone(&state, lone_id, Some(user_pk), event).await;
+    });
+}
+
+/// Moderation needs `privilege` and a higher rank than the target, so nobody can moderate
+/// the owner, themselves or anyone ranked as high as they are.
+async fn moderated_lone(
+    state: &AppState, jwt: &Jwt, id: u32, target: u32, privilege: RolePrivilege
+) -> Result<(lone::Model, i32), ServerResponse> {
+    let lone = privileged_lone(state, jwt, id, privilege).await?;
+    let target = UserId::from_encoded(target).decode() as i32;
+    let ranks = async {
+        anyhow::Ok((rank(state, &lone, user_pk(jwt)).await?, rank(state, &lone, target).await?))
+    };
+    let (moderator_rank, target_rank) = ranks.await.map_err(|e| {
+        println!("[Member(moderate)] Error: {}", e);
+        ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+    })?;
+    if target == user_pk(jwt) || target_rank >= moderator_rank {
+        return Err(ServerResponse::fine(ServerResponseError::DeniedLonePermission, None));
+    }
+    Ok((lone, target))
+}
+
+async fn get_members(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match lone_user::DB::from_state(&state).select_members(lone.id).await {
+        Ok(members) => {
+            let members = members.into_iter().map(encode_user).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "members": members })))
+        },
+        Err(e) => {
+            println!("[Member(get)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// The owner has to hand the lone over or delete it instead.
+async fn post_leave(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let user_pk = user_pk(&jwt);
+    if lone.owner_id == user_pk {
+        return ServerResponse::fine(ServerResponseError::RequiredLoneTransfer, None);
+    }
+    match lone_user::DB::from_state(&state).leave(lone.id, user_pk).await {
+        Ok(_) => {
+            state.permissions.invalidate_member(lone.id, user_pk);
+            announce(&state, lone.id, user_pk, Event::MemberLeave { user_id: encode_user(user_pk) });
+            ServerResponse::ok(None)
+        },
+        Err(e) => {
+            println!("[Member(leave)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Kicks a member, they can come back with another invite.
+async fn delete_member(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, user_id)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+    let (lone, target) = match moderated_lone(&state, &jwt, id, user_id, RolePrivilege::KICK_MEMBERS).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    match lone_user::DB::from_state(&state).leave(lone.id, target).await {
+        Ok(true) => {
+            state.permissions.invalidate_member(lone.id, target);
+            let event = Event::MemberKick {
+                user_id:        encode_user(target),
+                moderator_id:   encode_user(user_pk(&jwt)),
+            };
+            announce(&state, lone.id, target, event);
+            ServerResponse::ok(None)
+        },
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidLoneMember, None),
+        Err(e) => {
+            println!("[Member(kick)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+async fn get_bans(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::BAN_MEMBERS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match lone_ban::DB::from_state(&state).select_lone(lone.id, Utc::now().naive_utc()).await {
+        Ok(models) => {
+            let bans = models.iter().map(ban_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "bans": bans })))
+        },
+        Err(e) => {
+            println!("[Ban(get)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Bans a user, members get removed from the lone. Users outside it can be banned too.
+async fn put_ban(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, user_id)): Path<(u32, u32)>,
+    Json(params): Json<BanParams>,
+) -> impl IntoResponse {
+    if !params.is_valid() {
+        return ServerResponse::fine(ServerResponseError::InvalidBanParams, None);
+    }
+    let (lone, target) = match moderated_lone(&state, &jwt, id, user_id, RolePrivilege::BAN_MEMBERS).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    let reason = params.reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    let expire_at = params.expires_in
+        .map(|secs| Utc::now().naive_utc() + Duration::from_secs(secs));
+
+    let moderator = user_pk(&jwt);
+    let res = lone_ban::DB::from_state(&state)
+        .ban(lone.id, target, moderator, reason.clone(), expire_at).await;
+    match res {
+        Ok(_) => {
+            state.permissions.invalidate_member(lone.id, target);
+            let event = Event::MemberBan {
+                user_id:        encode_user(target),
+                moderator_id:   encode_user(moderator),
+                reason,
+                expire_at:      expire_at.map(|time| time.and_utc().timestamp_millis()),
+            };
+            announce(&state, lone.id, target, event);
+            ServerResponse::ok(None)
+        },
+        Err(e) => {
+            println!("[Ban(put)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+async fn delete_ban(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, user_id)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+    let (lone, target) = match moderated_lone(&state, &jwt, id, user_id, RolePrivilege::BAN_MEMBERS).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    match lone_ban::DB::from_state(&state).unban(lone.id, target).await {
+        Ok(true) => {
+            let event = Event::MemberUnban {
+                user_id:        encode_user(target),
+                moderator_id:   encode_user(user_pk(&jwt)),
+            };
+            announce(&state, lone.id, target, event);
+            ServerResponse::ok(None)
+        },
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidBanId, None),
+        Err(e) => {
+            println!("[Ban(delete)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+
+#[test]
+fn ban_params_test() {
+    let now = Utc::now().naive_utc();
+    let ban = lone_ban::Model {
+        lone_id:        1,
+        user_id:        2,
+        moderator_id:   Some(1),
+        reason:         None,
+        expire_at:      Some(now + Duration::from_secs(60)),
+        created_at:     now,
+    };
+    assert!(lone_ban::is_active(&ban, now));
+    assert!(!lone_ban::is_active(&ban, now + Duration::from_secs(60)));
+    assert!(lone_ban::is_active(&lone_ban::Model { expire_at: None, ..ban }, now));
+
+    assert!(BanParams { reason: Some("spam".to_string()), expires_in: Some(3600) }.is_valid());
+    assert!(!BanParams { reason: Some("x".repeat(257)), expires_in: None }.is_valid());
+    assert!(!BanParams { reason: None, expires_in: Some(0) }.is_valid());
+}
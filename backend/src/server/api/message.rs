@@ -0,0 +1,305 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    routing::{get, patch},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::{notify_room, Author, ChatContent, ChatText, Event, Payload, Scope};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    lone,
+    message,
+    message_reaction,
+    message_revision,
+    room,
+};
+use crate::sql::message::{Cursor, Target};
+use crate::id::{GeneralId, LoneId, MessageId, RoomId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::user_pk;
+use super::room::{member_room, MESSAGE_MAX_LEN};
+
+const HISTORY_DEFAULT_LIMIT: u64 = 50;
+const HISTORY_MAX_LIMIT: u64 = 100;
+
+/// At most one of `before`, `after` and `around`, none loads the latest messages.
+#[derive(Debug, Deserialize)]
+struct HistoryParams {
+    before:     Option<u32>,
+    after:      Option<u32>,
+    around:     Option<u32>,
+    limit:      Option<u64>,
+}
+
+impl HistoryParams {
+    fn cursor(&self) -> Option<Cursor> {
+        let decode = |id: u32| MessageId::from_encoded(id).decode() as i32;
+        match (self.before, self.after, self.around) {
+            (None, None, None) => Some(Cursor::Latest),
+            (Some(id), None, None) => Some(Cursor::Before(decode(id))),
+            (None, Some(id), None) => Some(Cursor::After(decode(id))),
+            (None, None, Some(id)) => Some(Cursor::Around(decode(id))),
+            _ => None,
+        }
+    }
+
+    fn limit(&self) -> Option<u64> {
+        let limit = self.limit.unwrap_or(HISTORY_DEFAULT_LIMIT);
+        (1..=HISTORY_MAX_LIMIT).contains(&limit).then_some(limit)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EditParams {
+    content:    String,
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/rooms/{id}/messages", get(get_messages))
+        .route("/messages/{msg_id}", patch(patch_message).delete(delete_message))
+        .route("/messages/{msg_id}/revisions", get(get_revisions))
+        .with_state(app_state)
+}
+
+fn revision_json(model: &message_revision::Model) -> Value {
+    json!({
+        "editor_id":    model.editor_id.map(|id| UserId::from_decoded(id as u32).encode()),
+        "action":       model.action,
+        "content":      model.content,
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+/// The content with its text replaced, keeping the format. Only text can be edited.
+fn edited(content: Value, body: String) -> Option<ChatContent> {
+    let len = body.chars().count();
+    if body.trim().is_empty() || len > MESSAGE_MAX_LEN {
+        return None;
+    }
+    match serde_json::from_value::<ChatContent>(content).ok()? {
+        ChatContent::Text(ChatText::PlainText { .. }) => Some(ChatContent::Text(ChatText::PlainText { body })),
+        ChatContent::Text(ChatText::Markdown { .. }) => Some(ChatContent::Text(ChatText::Markdown { body })),
+        _ => None,
+    }
+}
+
+/// A stored message in the shape the WebSocket delivers it. It isn't a signal, so it has
+/// no sequence number.
+pub(crate) fn message_payload(model: &message::Model) -> Option<Payload> {
+    let msg_id = MessageId::from_decoded(model.id as u32).encode();
+    let author = match model.author_id {
+        Some(user_id) => Author::User { id: UserId::from_decoded(user_id as u32).encode() },
+        None => Author::System,
+    };
+    let scope = match Target::of(model)? {
+        Target::Lone(lone_id) => Scope::Lone { lone_id: LoneId::from_decoded(lone_id as u32).encode() },
+        Target::Room { lone_id, room_id } => Scope::Room {
+            lone_id:    LoneId::from_decoded(lone_id as u32).encode(),
+            room_id:    RoomId::from_decoded(room_id as u32).encode(),
+        },
+        Target::Private(_) => Scope::Private,
+    };
+    let content = match serde_json::from_value(model.content.clone()) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("[Message(payload)] Error: message {}: {}", model.id, e);
+            return None;
+        }
+    };
+    let event = Event::Chat {
+        event_id:   msg_id,
+        content,
+        quote:      model.quote_id.map(|id| MessageId::from_decoded(id as u32).encode()),
+        edited_at:  model.edited_at.map(|at| at.and_utc().timestamp_millis()),
+    };
+    Some(Payload::new(0, author, scope, event))
+}
+
+fn db_err(tag: &str, e: anyhow::Error) -> ServerResponse {
+    println!("[Message({})] Error: {}", tag, e);
+    ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+}
+
+/// Loads a room message, deleted ones included, and the room the caller needs `privilege` in.
+pub(crate) async fn room_message(
+    state: &AppState, jwt: &Jwt, msg_id: u32, privilege: impl Fn(&message::Model) -> RolePrivilege
+) -> Result<(lone::Model, room::Model, message::Model), ServerResponse> {
+    let msg_pk = MessageId::from_encoded(msg_id).decode() as i32;
+    let model = match message::DB::from_state(state).select_pk(msg_pk).await {
+        Ok(Some(model)) => model,
+        Ok(None) => return Err(ServerResponse::fine(ServerResponseError::InvalidMessageId, None)),
+        Err(e) => return Err(db_err("get", e.into())),
+    };
+    let Some(Target::Room { room_id, .. }) = Target::of(&model) else {
+        return Err(ServerResponse::fine(ServerResponseError::InvalidMessageId, None));
+    };
+    let room_id = RoomId::from_decoded(room_id as u32).encode();
+    let (lone, room) = member_room(state, jwt, room_id, privilege(&model)).await?;
+    Ok((lone, room, model))
+}
+
+/// A page of the room's history, oldest first, with the reaction counts of its messages.
+async fn get_messages(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Query(params): Query<HistoryParams>,
+) -> impl IntoResponse {
+    let (Some(cursor), Some(limit)) = (params.cursor(), params.limit()) else {
+        return ServerResponse::fine(ServerResponseError::InvalidMessageParams, None);
+    };
+    let (_, room) = match member_room(&state, &jwt, id, RolePrivilege::VIEW_ROOM).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    let models = match message::DB::from_state(&state).select_room(room.id, cursor, limit).await {
+        Ok(models) => models,
+        Err(e) => return db_err("get", e),
+    };
+    let message_ids = models.iter().map(|model| model.id).collect::<Vec<_>>();
+    let counts = match message_reaction::DB::from_state(&state).select_counts(&message_ids, user_pk(&jwt)).await {
+        Ok(counts) => counts,
+        Err(e) => return db_err("get", e),
+    };
+
+    let messages = models.iter().filter_map(message_payload).collect::<Vec<_>>();
+    // keyed by message id, next to the payloads so they keep the WebSocket shape
+    let reactions = counts.iter()
+        .map(|(id, counts)| {
+            let counts = counts.iter()
+                .map(|c| json!({ "emoji": c.emoji, "count": c.count, "me": c.me }))
+                .collect::<Vec<_>>();
+            (MessageId::from_decoded(*id as u32).encode().to_string(), Value::from(counts))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    ServerResponse::ok(Some(json!({ "messages": messages, "reactions": reactions })))
+}
+
+/// Authors edit their own messages, the old content is kept as a revision.
+async fn patch_message(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(msg_id): Path<u32>,
+    Json(params): Json<EditParams>,
+) -> impl IntoResponse {
+    let user_id = user_pk(&jwt);
+    let (lone, room, model) = match room_message(&state, &jwt, msg_id, |_| RolePrivilege::SEND_MESSAGES).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    if model.deleted_at.is_some() {
+        return ServerResponse::fine(ServerResponseError::InvalidMessageId, None);
+    }
+    if model.author_id != Some(user_id) {
+        return ServerResponse::fine(ServerResponseError::DeniedMessageAuthor, None);
+    }
+    let Some(content) = edited(model.content, params.content) else {
+        return ServerResponse::fine(ServerResponseError::InvalidMessageParams, None);
+    };
+    let value = match serde_json::to_value(&content) {
+        Ok(value) => value,
+        Err(e) => return db_err("patch", e.into()),
+    };
+    let model = match message::DB::from_state(&state).edit(model.id, user_id, value).await {
+        Ok(Some(model)) => model,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::InvalidMessageId, None),
+        Err(e) => return db_err("patch", e),
+    };
+
+    let edited_at = model.edited_at.unwrap_or(model.created_at).and_utc().timestamp_millis();
+    let event = Event::ChatEdit { event_id: msg_id, content, edited_at };
+    let author = Author::User { id: UserId::from_decoded(user_id as u32).encode() };
+    tokio::spawn(async move {
+        notify_room(&state, &lone, room.id, author, event).await;
+    });
+    ServerResponse::ok(Some(json!({ "msg_id": msg_id, "edited_at": edited_at })))
+}
+
+/// Authors delete their own messages, members managing messages delete anyone's.
+async fn delete_message(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(msg_id): Path<u32>,
+) -> impl IntoResponse {
+    let user_id = user_pk(&jwt);
+    let privilege = |model: &message::Model| if model.author_id == Some(user_id) {
+        RolePrivilege::VIEW_ROOM
+    } else {
+        RolePrivilege::MANAGE_MESSAGES
+    };
+    let (lone, room, model) = match room_message(&state, &jwt, msg_id, privilege).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    match message::DB::from_state(&state).remove(model.id, user_id).await {
+        Ok(true) => {
+            let author = Author::User { id: UserId::from_decoded(user_id as u32).encode() };
+            tokio::spawn(async move {
+                notify_room(&state, &lone, room.id, author, Event::ChatDelete { event_id: msg_id }).await;
+            });
+            ServerResponse::ok(None)
+        },
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidMessageId, None),
+        Err(e) => db_err("delete", e),
+    }
+}
+
+/// What a message said before each edit or its deletion, for moderators.
+async fn get_revisions(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(msg_id): Path<u32>,
+) -> impl IntoResponse {
+    let (_, _, model) = match room_message(&state, &jwt, msg_id, |_| RolePrivilege::MANAGE_MESSAGES).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    match message_revision::DB::from_state(&state).select_message(model.id).await {
+        Ok(models) => {
+            let revisions = models.iter().map(revision_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({
+                "revisions":    revisions,
+                "deleted_at":   model.deleted_at.map(|at| at.and_utc().timestamp_millis()),
+            })))
+        },
+        Err(e) => db_err("get", e),
+    }
+}
+
+
+#[test]
+fn history_params_test() {
+    let params = |before, after, around, limit| HistoryParams { before, after, around, limit };
+    let id = MessageId::from_decoded(42u32).encode();
+
+    assert_eq!(params(None, None, None, None).cursor(), Some(Cursor::Latest));
+    assert_eq!(params(Some(id), None, None, None).cursor(), Some(Cursor::Before(42)));
+    assert_eq!(params(None, None, Some(id), None).cursor(), Some(Cursor::Around(42)));
+    assert_eq!(params(Some(id), Some(id), None, None).cursor(), None);
+
+    assert_eq!(params(None, None, None, None).limit(), Some(HISTORY_DEFAULT_LIMIT));
+    assert_eq!(params(None, None, None, Some(HISTORY_MAX_LIMIT)).limit(), Some(HISTORY_MAX_LIMIT));
+    assert_eq!(params(None, None, None, Some(HISTORY_MAX_LIMIT + 1)).limit(), None);
+    assert_eq!(params(None, None, None, Some(0)).limit(), None);
+}
+
+#[test]
+fn message_edit_test() {
+    let text = |text: ChatText| serde_json::to_value(ChatContent::Text(text)).unwrap();
+    let markdown = text(ChatText::Markdown { body: "*hi*".to_string() });
+    assert!(matches!(
+        edited(markdown.clone(), "**hi**".to_string()),
+        Some(ChatContent::Text(ChatText::Markdown { body })) if body == "**hi**"
+    ));
+    assert!(edited(markdown.clone(), "  ".to_string()).is_none());
+    assert!(edited(markdown, "a".repeat(MESSAGE_MAX_LEN + 1)).is_none());
+    assert!(edited(text(ChatText::Html { body: "<b>hi</b>".to_string() }), "hi".to_string()).is_none());
+}
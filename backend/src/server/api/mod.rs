@@ -1,5 +1,16 @@
+pub mod category;
+pub mod invite;
 pub mod login;
+pub mod lone;
+pub mod member;
+pub mod message;
 pub mod public;
+pub mod reaction;
 pub mod register;
+pub mod reset;
+pub mod role;
+pub mod room;
+pub mod session;
+pub mod token;
 pub mod tools;
-mod room;
\ No newline at end of file
+pub mod two_factor;
\ No newline at end of file
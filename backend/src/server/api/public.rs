@@ -1,12 +1,15 @@
 use crate::server::{fs_read, AppState};
 
+use axum::extract::State;
 use axum::response::Html;
-use axum::{routing::get, Router};
+use axum::{routing::get, Json, Router};
+use serde_json::Value;
 
 pub(crate) fn route(app_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/popup.js", get(popup))
         .route("/verify", get(get_verify))
+        .route("/.well-known/jwks.json", get(get_jwks))
         .with_state(app_state)
 }
 
@@ -17,3 +20,8 @@ async fn popup() -> Html<String> {
 async fn get_verify() -> Html<String> {
     Html(fs_read("../frontend/verify.html").await.unwrap())
 }
+
+/// Public keys other services can verify our access tokens with.
+async fn get_jwks(State(state): State<AppState>) -> Json<Value> {
+    Json(state.jwt_keys.jwks())
+}
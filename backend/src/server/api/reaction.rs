@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::put,
+    Router,
+};
+use serde_json::json;
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::{notify_room, Author, Event};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    DataBase,
+    message_reaction,
+};
+use crate::sql::message_reaction::React;
+use crate::id::{GeneralId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::user_pk;
+use super::message::room_message;
+
+/// Different emojis a message can carry, reacting again with one of them is always fine.
+const MAX_DISTINCT_REACTIONS: u64 = 20;
+const CUSTOM_EMOJI_PREFIX: &str = "custom:";
+const EMOJI_MAX_BYTES: usize = 64;
+
+const ZWJ: char = '\u{200D}';
+const VS16: char = '\u{FE0F}';
+const KEYCAP: char = '\u{20E3}';
+const BLACK_FLAG: char = '\u{1F3F4}';
+const CANCEL_TAG: char = '\u{E007F}';
+
+/// Roughly Unicode's Extended_Pictographic, without the regional indicators and skin tones
+/// that only count as part of a sequence.
+fn is_pictographic(c: char) -> bool {
+    matches!(c as u32,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..=0x2199 | 0x21A9..=0x21AA
+        | 0x231A..=0x231B | 0x2328 | 0x23CF | 0x23E9..=0x23F3 | 0x23F8..=0x23FA | 0x24C2
+        | 0x25AA..=0x25AB | 0x25B6 | 0x25C0 | 0x25FB..=0x25FE | 0x2600..=0x27BF | 0x2934..=0x2935
+        | 0x2B05..=0x2B07 | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55 | 0x3030 | 0x303D | 0x3297 | 0x3299
+        | 0x1F000..=0x1F1E5 | 0x1F200..=0x1F3FA | 0x1F400..=0x1FAFF | 0x1FC00..=0x1FFFD
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_skin_tone(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+fn is_tag(c: char) -> bool {
+    ('\u{E0020}'..='\u{E007E}').contains(&c)
+}
+
+/// Whether `raw` is exactly one emoji: a flag, a keycap, a tag sequence like the flag of
+/// Scotland, or pictographs joined by ZWJ, each with an optional VS16 and skin tone.
+fn is_emoji(raw: &str) -> bool {
+    let chars = raw.chars().collect::<Vec<_>>();
+    match chars.as_slice() {
+        [a, b] if is_regional_indicator(*a) && is_regional_indicator(*b) => return true,
+        [key, VS16, KEYCAP] | [key, KEYCAP] if matches!(key, '0'..='9' | '#' | '*') => return true,
+        [BLACK_FLAG, tags @ .., CANCEL_TAG] if !tags.is_empty() && tags.iter().all(|c| is_tag(*c)) => return true,
+        _ => {},
+    }
+    chars.split(|c| *c == ZWJ).all(|element| match element {
+        [c] | [c, VS16] => is_pictographic(*c),
+        [c, tone] | [c, VS16, tone] => is_pictographic(*c) && is_skin_tone(*tone),
+        _ => false,
+    })
+}
+
+/// The stored form of an emoji: the Unicode emoji itself, or "custom:" and the id of an
+/// emoji of the lone. Anything that doesn't look like either is refused.
+/// Custom emojis aren't stored anywhere yet, so their ids can't be checked against the lone.
+fn emoji_key(raw: &str) -> Option<String> {
+    if let Some(id) = raw.strip_prefix(CUSTOM_EMOJI_PREFIX) {
+        let id = id.parse::<u32>().ok()?;
+        return Some(format!("{}{}", CUSTOM_EMOJI_PREFIX, id));
+    }
+    (raw.len() <= EMOJI_MAX_BYTES && is_emoji(raw)).then(|| raw.to_string())
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/messages/{msg_id}/reactions/{emoji}", put(put_reaction).delete(delete_reaction))
+        .with_state(app_state)
+}
+
+fn db_err(tag: &str, e: anyhow::Error) -> ServerResponse {
+    println!("[Reaction({})] Error: {}", tag, e);
+    ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+}
+
+async fn put_reaction(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((msg_id, emoji)): Path<(u32, String)>,
+) -> impl IntoResponse {
+    let Some(emoji) = emoji_key(&emoji) else {
+        return ServerResponse::fine(ServerResponseError::InvalidReactionParams, None);
+    };
+    let (lone, room, model) = match room_message(&state, &jwt, msg_id, |_| RolePrivilege::SEND_MESSAGES).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    let user_id = user_pk(&jwt);
+    let reacted = message_reaction::DB::from_state(&state)
+        .react(model.id, user_id, &emoji, MAX_DISTINCT_REACTIONS).await;
+    let count = match reacted {
+        Ok(React::Added(count)) => count,
+        Ok(React::AlreadyReacted) => return ServerResponse::ok(None),
+        Ok(React::NotFound) => return ServerResponse::fine(ServerResponseError::InvalidMessageId, None),
+        Ok(React::LimitReached) => return ServerResponse::fine(ServerResponseError::ExceededReactionLimit, None),
+        Err(e) => return db_err("put", e),
+    };
+
+    let user_id = UserId::from_decoded(user_id as u32).encode();
+    let event = Event::ReactionAdd { event_id: msg_id, user_id, emoji: emoji.clone(), count };
+    tokio::spawn(async move {
+        notify_room(&state, &lone, room.id, Author::User { id: user_id }, event).await;
+    });
+    ServerResponse::ok(Some(json!({ "emoji": emoji, "count": count })))
+}
+
+async fn delete_reaction(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((msg_id, emoji)): Path<(u32, String)>,
+) -> impl IntoResponse {
+    let Some(emoji) = emoji_key(&emoji) else {
+        return ServerResponse::fine(ServerResponseError::InvalidReactionParams, None);
+    };
+    let (lone, room, model) = match room_message(&state, &jwt, msg_id, |_| RolePrivilege::VIEW_ROOM).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+    let user_id = user_pk(&jwt);
+    let count = match message_reaction::DB::from_state(&state).unreact(model.id, user_id, &emoji).await {
+        Ok(Some(count)) => count,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::InvalidReactionId, None),
+        Err(e) => return db_err("delete", e),
+    };
+
+    let user_id = UserId::from_decoded(user_id as u32).encode();
+    let event = Event::ReactionRemove { event_id: msg_id, user_id, emoji: emoji.clone(), count };
+    tokio::spawn(async move {
+        notify_room(&state, &lone, room.id, Author::User { id: user_id }, event).await;
+    });
+    ServerResponse::ok(Some(json!({ "emoji": emoji, "count": count })))
+}
+
+
+#[test]
+fn emoji_key_test() {
+    assert_eq!(emoji_key("👍").as_deref(), Some("👍"));
+    assert_eq!(emoji_key("👨‍👩‍👧‍👦").as_deref(), Some("👨‍👩‍👧‍👦"));
+    assert_eq!(emoji_key("1️⃣").as_deref(), Some("1️⃣"));
+    assert_eq!(emoji_key("custom:0042").as_deref(), Some("custom:42"));
+    for emoji in ["❤️", "👍🏽", "🧑🏽‍💻", "🏳️‍🌈", "🇯🇵", "#️⃣", "🏴\u{E0067}\u{E0062}\u{E0073}\u{E0063}\u{E0074}\u{E007F}"] {
+        assert_eq!(emoji_key(emoji).as_deref(), Some(emoji));
+    }
+
+    assert!(emoji_key("").is_none());
+    assert!(emoji_key("lol").is_none());
+    assert!(emoji_key("👍 👍").is_none());
+    assert!(emoji_key("custom:abc").is_none());
+    assert!(emoji_key(&"👍".repeat(EMOJI_MAX_BYTES)).is_none());
+    for text in ["中文", "é", "ñandú", "👍👍", "🇯", "🏽", "1", "\u{200D}👍", "👍\u{200D}"] {
+        assert!(emoji_key(text).is_none(), "{}", text);
+    }
+}
@@ -1,20 +1,30 @@
-use std::sync::OnceLock;
-use anyhow::anyhow;
+use std::time::Duration;
 use axum::{
     extract::State,
     response::{Html, IntoResponse},
     routing::{get, post},
     Json, Router,
 };
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
+use rand::Rng;
 use sea_orm::ActiveValue;
 use serde::Deserialize;
-use crate::server::{fs_read, AppState, ServerResponse, ServerResponseError};
+use serde_json::json;
+use tokio::time::Instant;
+use crate::email::Mailer;
+use crate::password;
+use crate::server::{fs_read, is_valid_email, AppState, ServerResponse, ServerResponseError};
+use crate::server::rate_limit::RateLimitLayer;
 use crate::sql::{
     BasicCRUD,
     DataBase,
     user,
 };
+
+const VERIFY_CODE_TTL: Duration = Duration::from_secs(10 * 60);
+const VERIFY_RESEND_COOLDOWN: Duration = Duration::from_secs(60);
+const VERIFY_MAX_ATTEMPTS: u32 = 5;
+
 #[derive(Debug, Deserialize)]
 struct RegisterParams {
     email:          Option<String>,
@@ -28,30 +38,204 @@ impl RegisterParams {
     }
 }
 
-impl TryInto<user::ActiveModel> for RegisterParams {
-    type Error = anyhow::Error;
-    fn try_into(self) -> Result<user::ActiveModel, Self::Error> {
-        if self.is_legal() {
-            Ok(user::ActiveModel {
-                email:      ActiveValue::Set(self.email.unwrap()),
-                username:   ActiveValue::Set(self.username.unwrap()),
-                password:   ActiveValue::Set(self.password.unwrap()),
-                ..Default::default()
-            })
-        } else {
-            Err(anyhow!("Invalid register params"))
+#[derive(Debug, Deserialize)]
+struct VerifyParams {
+    email:          Option<String>,
+    code:           Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResendParams {
+    email:          Option<String>,
+}
+
+#[derive(Debug)]
+struct PendingRegister {
+    username:   String,
+    password:   String, // already hashed
+    code:       u32,
+    attempts:   u32,
+    sent_at:    Instant,
+    expire_at:  Instant,
+}
+
+impl PendingRegister {
+    fn into_model(self, email: String) -> user::ActiveModel {
+        user::ActiveModel {
+            email:      ActiveValue::Set(email),
+            username:   ActiveValue::Set(self.username),
+            password:   ActiveValue::Set(self.password),
+            ..Default::default()
         }
     }
 }
 
-// struct RegisterSession(DashMap<u32, RegisterParams>);
-// 
-// static REGISTER_SESSION: OnceLock<RegisterSession> = RegisterSession(DashMap::new());
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum VerifyError {
+    NotFound,
+    Mismatch,
+    Expired,
+    TooManyAttempts,
+    Cooldown(Duration),
+    /// another registration of the email waits for its code
+    Pending,
+    Email,
+}
+
+impl From<VerifyError> for ServerResponse {
+    fn from(e: VerifyError) -> Self {
+        match e {
+            VerifyError::NotFound | VerifyError::Mismatch
+                => ServerResponse::fine(ServerResponseError::MismatchVerifyCode, None),
+            VerifyError::Expired
+                => ServerResponse::fine(ServerResponseError::ExpiredVerifyCode, None),
+            VerifyError::TooManyAttempts
+                => ServerResponse::fine(ServerResponseError::TooManyVerifyAttempts, None),
+            VerifyError::Cooldown(wait)
+                => ServerResponse::fine(
+                    ServerResponseError::FrequentVerifyResend,
+                    Some(json!({ "retry_after": wait.as_secs().max(1) })),
+                ),
+            VerifyError::Pending
+                => ServerResponse::fine(ServerResponseError::PendingRegisterEmail, None),
+            VerifyError::Email
+                => ServerResponse::inner_err(ServerResponseError::InternalEmailError),
+        }
+    }
+}
+
+/// Registrations waiting for their email verification code, keyed by email.
+#[derive(Debug)]
+pub(crate) struct RegisterSession {
+    pending:            DashMap<String, PendingRegister>,
+    code_ttl:           Duration,
+    resend_cooldown:    Duration,
+    max_attempts:       u32,
+}
+
+impl Default for RegisterSession {
+    fn default() -> Self {
+        Self::new(VERIFY_CODE_TTL, VERIFY_RESEND_COOLDOWN, VERIFY_MAX_ATTEMPTS)
+    }
+}
+
+impl RegisterSession {
+    pub fn new(code_ttl: Duration, resend_cooldown: Duration, max_attempts: u32) -> Self {
+        Self {
+            pending: DashMap::new(),
+            code_ttl,
+            resend_cooldown,
+            max_attempts,
+        }
+    }
+
+    fn gen_code() -> u32 {
+        rand::thread_rng().gen_range(100_000..1_000_000)
+    }
+
+    fn cooldown_left(&self, sent_at: Instant) -> Option<Duration> {
+        let elapsed = sent_at.elapsed();
+        if elapsed < self.resend_cooldown {
+            Some(self.resend_cooldown - elapsed)
+        } else {
+            None
+        }
+    }
+
+    /// Stores a pending registration and mails its verification code.
+    /// An email with a registration still pending is refused until it expires, so nobody can
+    /// slip their own credentials under a code mailed to someone else. `resend` is for lost codes.
+    pub async fn start(
+        &self,
+        mailer: &dyn Mailer,
+        email: &str,
+        username: String,
+        password: String,
+    ) -> Result<(), VerifyError> {
+        let now = Instant::now();
+        self.pending.retain(|_, p| p.expire_at > now);
+
+        let code = Self::gen_code();
+        match self.pending.entry(email.to_string()) {
+            Entry::Occupied(prev) if prev.get().expire_at > now => {
+                return Err(match self.cooldown_left(prev.get().sent_at) {
+                    Some(wait) => VerifyError::Cooldown(wait),
+                    None => VerifyError::Pending,
+                });
+            },
+            entry => {
+                entry.insert(PendingRegister {
+                    username,
+                    password,
+                    code,
+                    attempts:   0,
+                    sent_at:    now,
+                    expire_at:  now + self.code_ttl,
+                });
+            },
+        }
+
+        if let Err(e) = mailer.send_verify_code(email, code).await {
+            println!("[Register] Failed to send verify code: {}", e);
+            self.pending.remove(email);
+            return Err(VerifyError::Email);
+        }
+        Ok(())
+    }
+
+    /// Issues a fresh code for a pending registration and extends its expiry.
+    /// Wrong attempts still count against the new code.
+    pub async fn resend(&self, mailer: &dyn Mailer, email: &str) -> Result<(), VerifyError> {
+        let code = {
+            let mut pending = self.pending.get_mut(email).ok_or(VerifyError::NotFound)?;
+            if let Some(wait) = self.cooldown_left(pending.sent_at) {
+                return Err(VerifyError::Cooldown(wait));
+            }
+            let now = Instant::now();
+            pending.code = Self::gen_code();
+            pending.sent_at = now;
+            pending.expire_at = now + self.code_ttl;
+            pending.code
+        };
+
+        if let Err(e) = mailer.send_verify_code(email, code).await {
+            println!("[Register] Failed to resend verify code: {}", e);
+            return Err(VerifyError::Email);
+        }
+        Ok(())
+    }
+
+    /// Checks `code` and, when it matches, takes the pending registration out of the store.
+    fn verify(&self, email: &str, code: u32) -> Result<PendingRegister, VerifyError> {
+        let err = {
+            let mut pending = self.pending.get_mut(email).ok_or(VerifyError::NotFound)?;
+            if pending.expire_at <= Instant::now() {
+                VerifyError::Expired
+            } else if pending.code == code {
+                drop(pending);
+                return self.pending.remove(email)
+                    .map(|(_, p)| p)
+                    .ok_or(VerifyError::NotFound);
+            } else {
+                pending.attempts += 1;
+                if pending.attempts >= self.max_attempts {
+                    VerifyError::TooManyAttempts
+                } else {
+                    return Err(VerifyError::Mismatch);
+                }
+            }
+        };
+        self.pending.remove(email);
+        Err(err)
+    }
+}
 
 pub(crate) fn route(app_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/register", get(get_register))
-        .route("/register", post(post_register))
+        .route("/register", post(post_register).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .route("/verify", post(post_verify).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .route("/verify/resend", post(post_resend).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
         .with_state(app_state)
 }
 
@@ -65,7 +249,7 @@ async fn post_register(
 ) -> impl IntoResponse {
     println!("post(register) called with params: {:?}", params);
     let user_db = user::DB::from_state(&state);
-    if !params.is_legal() {
+    if !params.is_legal() || !is_valid_email(params.email.as_ref().unwrap()) {
         return ServerResponse::fine(ServerResponseError::InvalidRegisterParams, None);
     }
     let user = user_db.select_email(params.email.as_ref().unwrap()).await;
@@ -79,10 +263,135 @@ async fn post_register(
         return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
     }
 
-    let new_user: user::ActiveModel = params.try_into().unwrap();
-    if let Err(_) = user_db.insert(new_user).await {
+    let hash = match password::hash(params.password.clone().unwrap()).await {
+        Ok(hash) => hash,
+        Err(_) => return ServerResponse::inner_err(ServerResponseError::InternalUnknownError),
+    };
+    let RegisterParams { email, username, .. } = params;
+
+    match state.register_sessions
+        .start(state.mailer.as_ref(), &email.unwrap(), username.unwrap(), hash)
+        .await
+    {
+        Ok(_) => ServerResponse::ok(None),
+        Err(e) => e.into(),
+    }
+}
+
+async fn post_verify(
+    State(state): State<AppState>,
+    Json(params): Json<VerifyParams>,
+) -> impl IntoResponse {
+    println!("post(verify) called with params: {:?}", params);
+    let VerifyParams { email, code } = params;
+    let (email, code) = match (email, code.and_then(|c| c.trim().parse::<u32>().ok())) {
+        (Some(email), Some(code)) => (email, code),
+        _ => return ServerResponse::fine(ServerResponseError::InvalidVerifyParams, None),
+    };
+
+    let pending = match state.register_sessions.verify(&email, code) {
+        Ok(pending) => pending,
+        Err(e) => return e.into(),
+    };
+
+    let user_db = user::DB::from_state(&state);
+    match user_db.select_email(&email).await {
+        Ok(None) => {},
+        Ok(Some(_)) => return ServerResponse::fine(ServerResponseError::ExistRegisterEmail, None),
+        Err(e) => {
+            println!("[Verify(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    if let Err(_) = user_db.insert(pending.into_model(email)).await {
         ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
     } else {
         ServerResponse::ok(None)
     }
 }
+
+async fn post_resend(
+    State(state): State<AppState>,
+    Json(params): Json<ResendParams>,
+) -> impl IntoResponse {
+    println!("post(verify/resend) called with params: {:?}", params);
+    let Some(email) = params.email else {
+        return ServerResponse::fine(ServerResponseError::InvalidVerifyParams, None);
+    };
+
+    match state.register_sessions.resend(state.mailer.as_ref(), &email).await {
+        Ok(_) => ServerResponse::ok(None),
+        Err(e) => e.into(),
+    }
+}
+
+
+#[tokio::test]
+async fn register_session_test() {
+    use crate::email::MockMailer;
+    let mailer = MockMailer::default();
+    let email = "sb@chatalone.asia";
+    let code_of = |m: &MockMailer| m.last_to(email).unwrap().parse::<u32>().unwrap();
+
+    let session = RegisterSession::default();
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    let code = code_of(&mailer);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::Mismatch);
+    let pending = session.verify(email, code).unwrap();
+    assert_eq!(pending.username, "fuyu");
+    assert_eq!(session.verify(email, code).unwrap_err(), VerifyError::NotFound);
+
+    // registering again right away hits the resend cooldown
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    assert!(matches!(
+        session.start(&mailer, email, "fuyu".into(), "hash".into()).await,
+        Err(VerifyError::Cooldown(_))
+    ));
+
+    // nor can anyone else take over the pending registration once the cooldown is over
+    let session = RegisterSession::new(VERIFY_CODE_TTL, Duration::ZERO, VERIFY_MAX_ATTEMPTS);
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    let code = code_of(&mailer);
+    assert_eq!(
+        session.start(&mailer, email, "evil".into(), "evil".into()).await,
+        Err(VerifyError::Pending)
+    );
+    assert_eq!(session.verify(email, code).unwrap().username, "fuyu");
+
+    // wrong codes burn the pending registration
+    let session = RegisterSession::new(VERIFY_CODE_TTL, Duration::ZERO, 3);
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    let code = code_of(&mailer);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::Mismatch);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::Mismatch);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::TooManyAttempts);
+    assert_eq!(session.verify(email, code).unwrap_err(), VerifyError::NotFound);
+
+    // resend cooldown
+    let session = RegisterSession::default();
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    assert!(matches!(session.resend(&mailer, email).await, Err(VerifyError::Cooldown(_))));
+    assert_eq!(session.resend(&mailer, "nobody@chatalone.asia").await, Err(VerifyError::NotFound));
+    let session = RegisterSession::new(VERIFY_CODE_TTL, Duration::ZERO, VERIFY_MAX_ATTEMPTS);
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    let sent = mailer.sent.lock().unwrap().len();
+    session.resend(&mailer, email).await.unwrap();
+    assert_eq!(mailer.sent.lock().unwrap().len(), sent + 1);
+    assert!(session.verify(email, code_of(&mailer)).is_ok());
+
+    // resending doesn't give more guesses
+    let session = RegisterSession::new(VERIFY_CODE_TTL, Duration::ZERO, 3);
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    let code = code_of(&mailer);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::Mismatch);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::Mismatch);
+    session.resend(&mailer, email).await.unwrap();
+    let code = code_of(&mailer);
+    assert_eq!(session.verify(email, code + 1).unwrap_err(), VerifyError::TooManyAttempts);
+
+    // expiry
+    let session = RegisterSession::new(Duration::ZERO, Duration::ZERO, VERIFY_MAX_ATTEMPTS);
+    session.start(&mailer, email, "fuyu".into(), "hash".into()).await.unwrap();
+    assert_eq!(session.verify(email, code_of(&mailer)).unwrap_err(), VerifyError::Expired);
+}
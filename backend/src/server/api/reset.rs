@@ -0,0 +1,204 @@
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{is_valid_email, AppState, ServerResponse, ServerResponseError};
+use crate::server::rate_limit::RateLimitLayer;
+
+use crate::jwt::JwtKeys;
+use crate::password;
+use crate::server::api::{login, token};
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    user,
+};
+use crate::id::{GeneralId, UserId};
+
+const RESET_TOKEN_TTL: i64 = 30 * 60;
+
+#[derive(Debug, Deserialize)]
+struct ForgotParams {
+    email:      Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResetParams {
+    token:      Option<String>,
+    password:   Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ResetPayload {
+    user_id:    u32,
+    exp_time:   i64,
+}
+
+/// `<payload>.<kid>.<signature>`
+///
+/// The signature covers the user's current password hash as well, so the token stops
+/// verifying as soon as the password changes. That is what makes it single-use.
+#[derive(Debug)]
+struct ResetToken<'a> {
+    payload_b64:    &'a str,
+    payload:        ResetPayload,
+    kid:            &'a str,
+    signature:      Vec<u8>,
+}
+
+impl<'a> ResetToken<'a> {
+    fn content(payload_b64: &str, password_hash: &str) -> String {
+        format!("{payload_b64}.{password_hash}")
+    }
+
+    fn generate(keys: &JwtKeys, user_pk: u32, password_hash: &str, ttl_s: i64) -> anyhow::Result<String> {
+        let payload = ResetPayload {
+            user_id:    UserId::from_decoded(user_pk).encode(),
+            exp_time:   Utc::now().timestamp_millis() + ttl_s * 1000,
+        };
+        let payload_b64 = URL_SAFE_NO_PAD.encode(serde_json::to_string(&payload)?);
+        let (kid, signature) = keys.sign(Self::content(&payload_b64, password_hash).as_bytes())?;
+        Ok(format!("{}.{}.{}", payload_b64, kid, URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    fn parse(s: &'a str) -> Option<Self> {
+        let (payload_b64, rest) = s.split_once('.')?;
+        let (kid, signature) = rest.rsplit_once('.')?;
+        let payload = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload_b64).ok()?).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        Some(Self { payload_b64, payload, kid, signature })
+    }
+
+    fn user_pk(&self) -> u32 {
+        UserId::from_encoded(self.payload.user_id).decode()
+    }
+
+    fn verify(&self, keys: &JwtKeys, password_hash: &str) -> bool {
+        let content = Self::content(self.payload_b64, password_hash);
+        keys.verify(self.kid, content.as_bytes(), &self.signature)
+            && Utc::now().timestamp_millis() <= self.payload.exp_time
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/password/forgot", post(post_forgot).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .route("/password/reset", post(post_reset).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .with_state(app_state)
+}
+
+/// Always answers the same way once the email is well-formed. The lookup and the mail
+/// run in the background, so neither the body nor the timing tells registered emails apart.
+async fn post_forgot(
+    State(state): State<AppState>,
+    Json(params): Json<ForgotParams>,
+) -> impl IntoResponse {
+    let Some(email) = params.email.filter(|email| is_valid_email(email)) else {
+        return ServerResponse::fine(ServerResponseError::InvalidResetParams, None);
+    };
+
+    tokio::spawn(async move {
+        let user_model = match user::DB::from_state(&state).select_email(&email).await {
+            Ok(Some(user_model)) => user_model,
+            Ok(None) => return,
+            Err(e) => {
+                println!("[Forgot(post)] Error: {}", e);
+                return;
+            }
+        };
+        let token = ResetToken::generate(
+            &state.jwt_keys, user_model.id as u32, &user_model.password, RESET_TOKEN_TTL,
+        );
+        let sent = match token {
+            Ok(token) => state.mailer.send_reset_token(&email, &token).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            println!("[Forgot(post)] Failed to send reset email: {}", e);
+        }
+    });
+
+    ServerResponse::ok(None)
+}
+
+async fn post_reset(
+    State(state): State<AppState>,
+    Json(params): Json<ResetParams>,
+) -> impl IntoResponse {
+    let (Some(token), Some(new_password)) = (params.token, params.password) else {
+        return ServerResponse::fine(ServerResponseError::InvalidResetParams, None);
+    };
+    if new_password.is_empty() {
+        return ServerResponse::fine(ServerResponseError::InvalidResetParams, None);
+    }
+    let Some(token) = ResetToken::parse(&token) else {
+        return ServerResponse::fine(ServerResponseError::InvalidResetToken, None);
+    };
+
+    let db = user::DB::from_state(&state);
+    let user_model = match db.select_pk(token.user_pk() as i32).await {
+        Ok(Some(user_model)) => user_model,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::InvalidResetToken, None),
+        Err(e) => {
+            println!("[Reset(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+    if !token.verify(&state.jwt_keys, &user_model.password) {
+        return ServerResponse::fine(ServerResponseError::InvalidResetToken, None);
+    }
+
+    let hash = match password::hash(new_password).await {
+        Ok(hash) => hash,
+        Err(_) => return ServerResponse::inner_err(ServerResponseError::InternalUnknownError),
+    };
+    // Two requests racing with the same token: only the first one swaps the hash.
+    match db.replace_password(user_model.id, &user_model.password, hash).await {
+        Ok(true) => {},
+        Ok(false) => return ServerResponse::fine(ServerResponseError::InvalidResetToken, None),
+        Err(e) => {
+            println!("[Reset(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    if let Err(e) = login::revoke_all(&state, user_model.id as u32).await {
+        println!("[Reset(post)] Failed to revoke sessions: {}", e);
+        return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+    }
+    token::clear_cookies(ServerResponse::ok(None))
+}
+
+
+#[test]
+fn reset_token_test() {
+    use crate::jwt::test_keys;
+    use serde_json::json;
+
+    let keys = test_keys(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }]
+    }));
+    let token = ResetToken::generate(&keys, 114514, "old_hash", 60).unwrap();
+    let parsed = ResetToken::parse(&token).unwrap();
+    assert_eq!(parsed.user_pk(), 114514);
+    assert!(parsed.verify(&keys, "old_hash"));
+
+    // the password changed, the token is spent
+    assert!(!parsed.verify(&keys, "new_hash"));
+
+    let expired = ResetToken::generate(&keys, 114514, "old_hash", -1).unwrap();
+    assert!(!ResetToken::parse(&expired).unwrap().verify(&keys, "old_hash"));
+
+    let other = ResetToken::generate(&keys, 1919810, "old_hash", 60).unwrap();
+    let (other_payload, _) = other.split_once('.').unwrap();
+    let (_, rest) = token.split_once('.').unwrap();
+    let forged = format!("{}.{}", other_payload, rest);
+    assert!(!ResetToken::parse(&forged).unwrap().verify(&keys, "old_hash"));
+    assert!(ResetToken::parse("not.a-token").is_none());
+}
@@ -0,0 +1,358 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, patch, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    lone,
+    lone_user,
+    role,
+};
+use crate::id::{GeneralId, LoneId, RoleId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use super::lone::{member_lone, privileged_lone, user_pk};
+
+const ROLE_NAME_MAX_LEN: usize = 32;
+/// Ranks above every role.
+const OWNER_RANK: i32 = i32::MAX;
+
+#[derive(Debug, Deserialize)]
+struct RoleParams {
+    name:       Option<String>,
+    privileges: Option<RolePrivilege>,
+    position:   Option<i32>,
+}
+
+impl RoleParams {
+    /// The trimmed name, `Err` if it's given but unusable. "@everyone" is reserved.
+    fn name(&self) -> Result<Option<String>, ()> {
+        let Some(name) = self.name.as_ref().map(|name| name.trim()) else {
+            return Ok(None);
+        };
+        let len = name.chars().count();
+        if len == 0 || len > ROLE_NAME_MAX_LEN || name == lone::EVERYONE_ROLE {
+            return Err(());
+        }
+        Ok(Some(name.to_string()))
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones/{id}/roles", get(get_roles).post(post_role))
+        .route("/lones/{id}/roles/{role_id}", patch(patch_role).delete(delete_role))
+        .route("/lones/{id}/members/{user_id}/roles/{role_id}", put(put_member_role).delete(delete_member_role))
+        .with_state(app_state)
+}
+
+fn role_json(model: &role::Model) -> Value {
+    json!({
+        "id":           RoleId::from_decoded(model.id as u32).encode(),
+        "lone_id":      LoneId::from_decoded(model.lone_id as u32).encode(),
+        "name":         model.name,
+        "position":     model.position,
+        "privileges":   RolePrivilege::from(model.privilege),
+    })
+}
+
+/// Who is managing roles: their rank and what they may hand out.
+#[derive(Debug, Copy, Clone)]
+struct Manager {
+    rank:       i32,
+    privilege:  RolePrivilege,
+}
+
+impl Manager {
+    /// Roles ranked below the manager's highest role are theirs to manage.
+    fn outranks(&self, role: &role::Model) -> bool {
+        role.position < self.rank
+    }
+
+    /// Privileges can only be granted by someone who holds them.
+    fn may_grant(&self, from: RolePrivilege, to: RolePrivilege) -> bool {
+        self.privilege.allows(to - from)
+    }
+
+    /// New roles go right above "@everyone" and push the others up, so any role of
+    /// the manager's own still ranks above it.
+    fn may_create(&self, privilege: RolePrivilege) -> bool {
+        self.rank >= 1 && self.may_grant(RolePrivilege::empty(), privilege)
+    }
+
+    /// Handing out a role, or taking it back, needs both the rank and every privilege it carries.
+    fn may_assign(&self, role: &role::Model) -> bool {
+        self.outranks(role) && self.may_grant(RolePrivilege::empty(), RolePrivilege::from(role.privilege))
+    }
+}
+
+fn denied() -> ServerResponse {
+    ServerResponse::fine(ServerResponseError::DeniedRoleHierarchy, None)
+}
+
+fn db_err(tag: &str, e: anyhow::Error) -> ServerResponse {
+    println!("[Role({})] Error: {}", tag, e);
+    ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+}
+
+/// Where the user ranks in the lone: the owner above everyone, members by their highest role,
+/// and those holding no role at all below every role.
+pub(crate) async fn rank(state: &AppState, lone: &lone::Model, user_pk: i32) -> Result<i32, anyhow::Error> {
+    if lone.owner_id == user_pk {
+        return Ok(OWNER_RANK);
+    }
+    let position = role::DB::from_state(state).highest_position(lone.id, user_pk).await?;
+    Ok(position.unwrap_or(-1))
+}
+
+async fn manager(state: &AppState, jwt: &Jwt, id: u32) -> Result<(lone::Model, Manager), ServerResponse> {
+    let lone = privileged_lone(state, jwt, id, RolePrivilege::MANAGE_ROLES).await?;
+    let user_pk = user_pk(jwt);
+    let privilege = state.permissions.in_lone(&state.db_conn, &lone, user_pk).await
+        .map_err(|e| db_err("manager", e))?;
+    let rank = rank(state, &lone, user_pk).await
+        .map_err(|e| db_err("manager", e))?;
+    Ok((lone, Manager { rank, privilege }))
+}
+
+/// Loads a role of the lone, roles of other lones count as missing.
+async fn lone_role(state: &AppState, lone: &lone::Model, role_id: u32) -> Result<role::Model, ServerResponse> {
+    let role_pk = RoleId::from_encoded(role_id).decode() as i32;
+    match role::DB::from_state(state).select_pk(role_pk).await {
+        Ok(Some(role)) if role.lone_id == lone.id => Ok(role),
+        Ok(_) => Err(ServerResponse::fine(ServerResponseError::InvalidRoleId, None)),
+        Err(e) => Err(db_err("get", e.into())),
+    }
+}
+
+async fn get_roles(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match role::DB::from_state(&state).select_lone(lone.id).await {
+        Ok(models) => {
+            let roles = models.iter().map(role_json).collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "roles": roles })))
+        },
+        Err(e) => db_err("get", e),
+    }
+}
+
+/// New roles start right above "@everyone".
+async fn post_role(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<RoleParams>,
+) -> impl IntoResponse {
+    let Ok(Some(name)) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidRoleParams, None);
+    };
+    let (lone, manager) = match manager(&state, &jwt, id).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    let privilege = params.privileges.unwrap_or_default();
+    if !manager.may_create(privilege) {
+        return denied();
+    }
+    match role::DB::from_state(&state).create(lone.id, name, privilege).await {
+        Ok(model) => ServerResponse::ok(Some(role_json(&model))),
+        Err(e) => db_err("post", e),
+    }
+}
+
+/// Renames, sets the privileges of or moves a role. "@everyone" only takes privileges.
+async fn patch_role(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, role_id)): Path<(u32, u32)>,
+    Json(params): Json<RoleParams>,
+) -> impl IntoResponse {
+    let Ok(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidRoleParams, None);
+    };
+    let (lone, manager) = match manager(&state, &jwt, id).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    let model = match lone_role(&state, &lone, role_id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    let is_everyone = model.name == lone::EVERYONE_ROLE;
+    if is_everyone && (name.is_some() || params.position.is_some()) {
+        return ServerResponse::fine(ServerResponseError::InvalidRoleParams, None);
+    }
+    if !manager.outranks(&model) {
+        return denied();
+    }
+    if let Some(privilege) = params.privileges {
+        if !manager.may_grant(RolePrivilege::from(model.privilege), privilege) {
+            return denied();
+        }
+    }
+    // can't be moved up to or above the manager's own rank
+    if params.position.is_some_and(|position| position < 1 || position >= manager.rank) {
+        return denied();
+    }
+
+    let db = role::DB::from_state(&state);
+    let mut model = model;
+    if name.is_some() || params.privileges.is_some() {
+        model = match db.update(model.id, params.privileges, name).await {
+            Ok(model) => model,
+            Err(e) => return db_err("patch", e),
+        };
+        state.permissions.invalidate_lone(lone.id);
+    }
+    if let Some(position) = params.position {
+        // keep positions contiguous
+        let top = match db.select_lone(lone.id).await {
+            Ok(roles) => roles.first().map(|role| role.position).unwrap_or(1),
+            Err(e) => return db_err("patch", e),
+        };
+        model = match db.move_to(&model, position.min(top)).await {
+            Ok(model) => model,
+            Err(e) => return db_err("patch", e),
+        };
+    }
+    ServerResponse::ok(Some(role_json(&model)))
+}
+
+async fn delete_role(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, role_id)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+    let (lone, manager) = match manager(&state, &jwt, id).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    let model = match lone_role(&state, &lone, role_id).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    if model.name == lone::EVERYONE_ROLE {
+        return ServerResponse::fine(ServerResponseError::InvalidRoleParams, None);
+    }
+    if !manager.outranks(&model) {
+        return denied();
+    }
+    match role::DB::from_state(&state).remove(&model).await {
+        Ok(_) => {
+            state.permissions.invalidate_lone(lone.id);
+            ServerResponse::ok(None)
+        },
+        Err(e) => db_err("delete", e),
+    }
+}
+
+/// Loads what assigning `role_id` to `user_id` needs, both must be the manager's to handle.
+async fn member_role(
+    state: &AppState, jwt: &Jwt, id: u32, user_id: u32, role_id: u32
+) -> Result<(lone::Model, i32, role::Model), ServerResponse> {
+    let (lone, manager) = manager(state, jwt, id).await?;
+    let model = lone_role(state, &lone, role_id).await?;
+    if model.name == lone::EVERYONE_ROLE {
+        return Err(ServerResponse::fine(ServerResponseError::InvalidRoleParams, None));
+    }
+    if !manager.may_assign(&model) {
+        return Err(denied());
+    }
+    let target = UserId::from_encoded(user_id).decode() as i32;
+    match lone_user::DB::from_state(state).select_privileges(lone.id, target).await {
+        Ok(roles) if !roles.is_empty() => Ok((lone, target, model)),
+        Ok(_) => Err(ServerResponse::fine(ServerResponseError::InvalidLoneMember, None)),
+        Err(e) => Err(db_err("member", e)),
+    }
+}
+
+async fn put_member_role(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, user_id, role_id)): Path<(u32, u32, u32)>,
+) -> impl IntoResponse {
+    let (lone, target, model) = match member_role(&state, &jwt, id, user_id, role_id).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    match lone_user::DB::from_state(&state).assign(lone.id, target, model.id).await {
+        Ok(_) => {
+            state.permissions.invalidate_member(lone.id, target);
+            ServerResponse::ok(None)
+        },
+        Err(e) => db_err("assign", e),
+    }
+}
+
+async fn delete_member_role(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, user_id, role_id)): Path<(u32, u32, u32)>,
+) -> impl IntoResponse {
+    let (lone, target, model) = match member_role(&state, &jwt, id, user_id, role_id).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+    match lone_user::DB::from_state(&state).unassign(lone.id, target, model.id).await {
+        Ok(true) => {
+            state.permissions.invalidate_member(lone.id, target);
+            ServerResponse::ok(None)
+        },
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidRoleId, None),
+        Err(e) => db_err("unassign", e),
+    }
+}
+
+
+#[test]
+fn role_hierarchy_test() {
+    let role = |position| role::Model {
+        id:         1,
+        name:       "mod".to_string(),
+        lone_id:    1,
+        privilege:  RolePrivilege::KICK_MEMBERS.into(),
+        position,
+    };
+    let manager = Manager { rank: 3, privilege: RolePrivilege::MANAGE_ROLES | RolePrivilege::KICK_MEMBERS };
+    assert!(manager.outranks(&role(2)));
+    assert!(!manager.outranks(&role(3)));
+    assert!(manager.may_grant(RolePrivilege::empty(), RolePrivilege::KICK_MEMBERS));
+    assert!(!manager.may_grant(RolePrivilege::empty(), RolePrivilege::BAN_MEMBERS));
+    // taking away what they don't hold themselves is fine
+    assert!(manager.may_grant(RolePrivilege::BAN_MEMBERS, RolePrivilege::empty()));
+
+    // a lower role still can't carry privileges the manager lacks onto a member
+    assert!(manager.may_assign(&role(2)));
+    assert!(!manager.may_assign(&role(3)));
+    let admin = role::Model { privilege: RolePrivilege::ADMINISTRATOR.into(), ..role(1) };
+    assert!(!manager.may_assign(&admin));
+    let admin_manager = Manager { rank: 3, privilege: RolePrivilege::ADMINISTRATOR };
+    assert!(admin_manager.may_assign(&admin));
+
+    // the lowest role above "@everyone" ends up above what it creates, "@everyone" doesn't
+    let lowest = Manager { rank: 1, ..manager };
+    assert!(lowest.may_create(RolePrivilege::KICK_MEMBERS));
+    assert!(!lowest.may_create(RolePrivilege::BAN_MEMBERS));
+    assert!(!Manager { rank: 0, ..manager }.may_create(RolePrivilege::empty()));
+
+    let params = |name: &str| RoleParams { name: Some(name.to_string()), privileges: None, position: None };
+    assert_eq!(params(" mod ").name(), Ok(Some("mod".to_string())));
+    assert!(params("@everyone").name().is_err());
+    assert!(params(&"r".repeat(33)).name().is_err());
+    assert_eq!(RoleParams { name: None, privileges: None, position: None }.name(), Ok(None));
+}
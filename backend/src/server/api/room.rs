@@ -1,27 +1,288 @@
+use std::collections::HashSet;
+
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, patch, post},
     Json, Router,
 };
-use axum_extra::extract::cookie;
 use serde::Deserialize;
+use serde_json::{json, Value};
 
-use crate::server::{
-    fs_read, is_valid_email, AppState, ServerResponse, ServerResponseError, JWT_EXPIRE_DURATION,
-};
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::websocket::{notify_room, notify_users, notify_visible_rooms, room_viewers, Author, ChatContent, ChatText, Event};
 
 use crate::jwt::Jwt;
 use crate::sql::{
+    BasicCRUD,
     DataBase,
-    user,
+    lone,
+    message,
+    room,
 };
-use crate::id::{GeneralId, UserId};
+use crate::sql::message::Target;
+use crate::id::{CategoryId, GeneralId, LoneId, MessageId, RoomId, UserId};
+use crate::entities::lone_role_info::RolePrivilege;
+use crate::entities::room_info::RoomType;
+use super::lone::{member_lone, privileged_lone, user_pk};
+
+const ROOM_NAME_MAX_LEN: usize = 32;
+pub(crate) const MESSAGE_MAX_LEN: usize = 4000;
+
+#[derive(Debug, Deserialize)]
+struct RoomParams {
+    name:       Option<String>,
+    r#type:     Option<String>,
+}
+
+impl RoomParams {
+    fn name(&self) -> Option<String> {
+        let name = self.name.as_ref()?.trim();
+        let len = name.chars().count();
+        (len > 0 && len <= ROOM_NAME_MAX_LEN).then(|| name.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReorderParams {
+    /// the category whose rooms are reordered, none for the rooms without one
+    category_id:    Option<u32>,
+    room_ids:       Vec<u32>,
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/lones/{id}/rooms", get(get_rooms).post(post_room).put(put_rooms))
+        .route("/lones/{id}/rooms/{room_id}", patch(patch_room).delete(delete_room))
+        .route("/messages", post(post_message))
+        .with_state(app_state)
+}
+
+pub(crate) fn room_json(model: &room::Model) -> Value {
+    json!({
+        "id":           RoomId::from_decoded(model.id as u32).encode(),
+        "lone_id":      LoneId::from_decoded(model.lone_id as u32).encode(),
+        "name":         model.name,
+        "type":         model.room_type,
+        "position":     model.position,
+        "category_id":  model.category_id.map(|id| CategoryId::from_decoded(id as u32).encode()),
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+    })
+}
+
+fn db_err(tag: &str, e: anyhow::Error) -> ServerResponse {
+    println!("[Room({})] Error: {}", tag, e);
+    ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+}
+
+/// Sends a room event to the members who can see the room.
+fn announce(state: &AppState, lone: &lone::Model, room_id: i32, event: Event) {
+    let (state, lone) = (state.clone(), lone.clone());
+    tokio::spawn(async move {
+        let users = room_viewers(&state, &lone, room_id).await;
+        notify_users(&state, lone.id, &users, event).await;
+    });
+}
+
+/// Loads a room of the lone the caller needs `privilege` in.
+/// Rooms they can't see look the same as missing ones.
+pub(crate) async fn lone_room(
+    state: &AppState, jwt: &Jwt, lone: &lone::Model, room_id: u32, privilege: RolePrivilege
+) -> Result<room::Model, ServerResponse> {
+    let room_pk = RoomId::from_encoded(room_id).decode() as i32;
+    let model = match room::DB::from_state(state).select_pk(room_pk).await {
+        Ok(Some(model)) if model.lone_id == lone.id => model,
+        Ok(_) => return Err(ServerResponse::fine(ServerResponseError::InvalidRoomId, None)),
+        Err(e) => return Err(db_err("get", e.into())),
+    };
+    let held = state.permissions.in_room(&state.db_conn, lone, model.id, user_pk(jwt)).await
+        .map_err(|e| db_err("get", e))?;
+    if !held.allows(RolePrivilege::VIEW_ROOM) {
+        return Err(ServerResponse::fine(ServerResponseError::InvalidRoomId, None));
+    }
+    if !held.allows(privilege) {
+        return Err(ServerResponse::fine(ServerResponseError::DeniedLonePermission, None));
+    }
+    Ok(model)
+}
+
+/// Like [`lone_room`], for routes that only name the room.
+pub(crate) async fn member_room(
+    state: &AppState, jwt: &Jwt, room_id: u32, privilege: RolePrivilege
+) -> Result<(lone::Model, room::Model), ServerResponse> {
+    let room_pk = RoomId::from_encoded(room_id).decode() as i32;
+    let lone_id = match room::DB::from_state(state).lone_of(room_pk).await {
+        Ok(Some(lone_id)) => lone_id,
+        Ok(None) => return Err(ServerResponse::fine(ServerResponseError::InvalidRoomId, None)),
+        Err(e) => return Err(db_err("get", e)),
+    };
+    let lone = member_lone(state, jwt, LoneId::from_decoded(lone_id as u32).encode()).await?;
+    let model = lone_room(state, jwt, &lone, room_id, privilege).await?;
+    Ok((lone, model))
+}
+
+/// Rooms of the lone the caller can see, in order.
+async fn get_rooms(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let models = match room::DB::from_state(&state).select_lone(lone.id).await {
+        Ok(models) => models,
+        Err(e) => return db_err("get", e),
+    };
+    let mut rooms = vec![];
+    for model in &models {
+        match state.permissions.in_room(&state.db_conn, &lone, model.id, user_pk(&jwt)).await {
+            Ok(held) if held.allows(RolePrivilege::VIEW_ROOM) => rooms.push(room_json(model)),
+            Ok(_) => {},
+            Err(e) => return db_err("get", e),
+        }
+    }
+    ServerResponse::ok(Some(json!({ "rooms": rooms })))
+}
+
+async fn post_room(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<RoomParams>,
+) -> impl IntoResponse {
+    let Some(name) = params.name() else {
+        return ServerResponse::fine(ServerResponseError::InvalidRoomParams, None);
+    };
+    let room_type = match params.r#type.as_deref().unwrap_or("text").parse::<RoomType>() {
+        Ok(room_type) => room_type,
+        Err(e) => return ServerResponse::fine(ServerResponseError::InvalidRoomType, Some(json!({ "reason": e.to_string() }))),
+    };
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    match room::DB::from_state(&state).create(lone.id, name, room_type).await {
+        Ok(model) => {
+            announce(&state, &lone, model.id, Event::RoomCreate { room: room_json(&model) });
+            ServerResponse::ok(Some(room_json(&model)))
+        },
+        Err(e) => db_err("post", e),
+    }
+}
+
+async fn patch_room(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, room_id)): Path<(u32, u32)>,
+    Json(params): Json<RoomParams>,
+) -> impl IntoResponse {
+    // the type of a room is fixed
+    let (Some(name), None) = (params.name(), &params.r#type) else {
+        return ServerResponse::fine(ServerResponseError::InvalidRoomParams, None);
+    };
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let model = match lone_room(&state, &jwt, &lone, room_id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    match room::DB::from_state(&state).rename(model.id, name).await {
+        Ok(model) => {
+            announce(&state, &lone, model.id, Event::RoomUpdate { room: room_json(&model) });
+            ServerResponse::ok(Some(room_json(&model)))
+        },
+        Err(e) => db_err("patch", e),
+    }
+}
+
+async fn delete_room(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path((id, room_id)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+    let lone = match member_lone(&state, &jwt, id).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let model = match lone_room(&state, &jwt, &lone, room_id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(model) => model,
+        Err(res) => return res,
+    };
+    // the room's overrides go with it, so who could see it is read first
+    let viewers = room_viewers(&state, &lone, model.id).await;
+    match room::DB::from_state(&state).delete_pk(model.id).await {
+        Ok(_) => {
+            state.permissions.invalidate_lone(lone.id);
+            let event = Event::RoomDelete { room_id: RoomId::from_decoded(model.id as u32).encode() };
+            tokio::spawn(async move {
+                notify_users(&state, lone.id, &viewers, event).await;
+            });
+            ServerResponse::ok(None)
+        },
+        Err(e) => db_err("delete", e.into()),
+    }
+}
+
+/// Reorders the rooms of one category, or those without a category, at once.
+/// Only the rooms the caller can see are named, and only those come back.
+async fn put_rooms(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(params): Json<ReorderParams>,
+) -> impl IntoResponse {
+    let lone = match privileged_lone(&state, &jwt, id, RolePrivilege::MANAGE_ROOMS).await {
+        Ok(lone) => lone,
+        Err(res) => return res,
+    };
+    let category_pk = params.category_id.map(|id| CategoryId::from_encoded(id).decode() as i32);
+    let room_ids = params.room_ids.iter()
+        .map(|id| RoomId::from_encoded(*id).decode() as i32)
+        .collect::<Vec<_>>();
+    let db = room::DB::from_state(&state);
+    // the caller orders the rooms they can see, the hidden ones stay put
+    let listed = match db.select_list(lone.id, category_pk).await {
+        Ok(models) => models,
+        Err(e) => return db_err("put", e),
+    };
+    let mut visible = HashSet::new();
+    for model in &listed {
+        match state.permissions.in_room(&state.db_conn, &lone, model.id, user_pk(&jwt)).await {
+            Ok(held) if held.allows(RolePrivilege::VIEW_ROOM) => { visible.insert(model.id); },
+            Ok(_) => {},
+            Err(e) => return db_err("put", e),
+        }
+    }
+    match db.reorder(lone.id, category_pk, &room_ids, &visible).await {
+        Ok(Some(models)) => {
+            // everyone learns the order of the rooms they can see only
+            let category_id = params.category_id;
+            let order = models.iter().map(|model| model.id).collect::<Vec<_>>();
+            let (state, lone) = (state.clone(), lone.clone());
+            tokio::spawn(async move {
+                notify_visible_rooms(&state, &lone, &order, |room_ids| {
+                    Event::RoomReorder { category_id, room_ids }
+                }).await;
+            });
+            let rooms = models.iter()
+                .filter(|model| visible.contains(&model.id))
+                .map(room_json)
+                .collect::<Vec<_>>();
+            ServerResponse::ok(Some(json!({ "rooms": rooms })))
+        },
+        Ok(None) => ServerResponse::fine(ServerResponseError::InvalidRoomParams, None),
+        Err(e) => db_err("put", e),
+    }
+}
+
 
 /// req:
 /// {
-///     type: enum{ plain | markdown | image | file },
+///     type:       enum{ "1": plain | "2": markdown },
 ///     room_id:    u32,
 ///     content:    String,
 ///     quote_id:   Option<u32>,
@@ -29,11 +290,8 @@ use crate::id::{GeneralId, UserId};
 /// ret:
 /// {
 ///     msg_id:     u32;
-///     timestamp:  u32;
+///     timestamp:  i64;    // ms
 /// }
-/// 
-///
-
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum RoomMessageParams {
@@ -43,11 +301,66 @@ enum RoomMessageParams {
     Markdown  { room_id: u32, content: String, quote_id: Option<u32> },
 }
 
+impl RoomMessageParams {
+    fn room_id(&self) -> u32 {
+        match self {
+            RoomMessageParams::PlainText { room_id, .. }
+            | RoomMessageParams::Markdown { room_id, .. } => *room_id,
+        }
+    }
+
+    fn quote_id(&self) -> Option<u32> {
+        match self {
+            RoomMessageParams::PlainText { quote_id, .. }
+            | RoomMessageParams::Markdown { quote_id, .. } => *quote_id,
+        }
+    }
+
+    /// The content to store, if it isn't blank or too long.
+    fn content(self) -> Option<ChatContent> {
+        let (text, body) = match self {
+            RoomMessageParams::PlainText { content, .. } => (ChatText::PlainText { body: content.clone() }, content),
+            RoomMessageParams::Markdown { content, .. } => (ChatText::Markdown { body: content.clone() }, content),
+        };
+        let len = body.chars().count();
+        (!body.trim().is_empty() && len <= MESSAGE_MAX_LEN).then_some(ChatContent::Text(text))
+    }
+}
+
 async fn post_message(
     jwt: Jwt,
     State(state): State<AppState>,
     Json(params): Json<RoomMessageParams>,
 ) -> impl IntoResponse {
-    let user_id = UserId::from_encoded(jwt.user_id() as u32).decode();
-    
-}
\ No newline at end of file
+    let room_id = params.room_id();
+    let quote_id = params.quote_id();
+    let Some(content) = params.content() else {
+        return ServerResponse::fine(ServerResponseError::InvalidMessageParams, None);
+    };
+    let (lone, model) = match member_room(&state, &jwt, room_id, RolePrivilege::SEND_MESSAGES).await {
+        Ok(found) => found,
+        Err(res) => return res,
+    };
+
+    let user_id = user_pk(&jwt);
+    let target = Target::Room { lone_id: lone.id, room_id: model.id };
+    let quote_pk = quote_id.map(|id| MessageId::from_encoded(id).decode() as i32);
+    let value = match serde_json::to_value(&content) {
+        Ok(value) => value,
+        Err(e) => return db_err("message", e.into()),
+    };
+    let message = match message::DB::from_state(&state).create(Some(user_id), target, value, quote_pk).await {
+        Ok(Some(message)) => message,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::InvalidMessageId, None),
+        Err(e) => return db_err("message", e),
+    };
+
+    let msg_id = MessageId::from_decoded(message.id as u32).encode();
+    let timestamp = message.created_at.and_utc().timestamp_millis();
+    let event = Event::Chat { event_id: msg_id, content, quote: quote_id, edited_at: None };
+    let author = Author::User { id: UserId::from_decoded(user_id as u32).encode() };
+    tokio::spawn(async move {
+        notify_room(&state, &lone, model.id, author, event).await;
+    });
+    ServerResponse::ok(Some(json!({ "msg_id": msg_id, "timestamp": timestamp })))
+}
@@ -0,0 +1,89 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get},
+    Router,
+};
+use serde_json::json;
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+
+use crate::jwt::Jwt;
+use crate::server::api::token;
+use crate::server::websocket::ws;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    refresh_token,
+    session,
+};
+use crate::id::{GeneralId, UserId};
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/sessions", get(get_sessions))
+        .route("/sessions/{id}", delete(delete_session))
+        .with_state(app_state)
+}
+
+async fn get_sessions(
+    jwt: Jwt,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode();
+    let models = match session::DB::from_state(&state).select_user(user_pk as i32).await {
+        Ok(models) => models,
+        Err(e) => {
+            println!("[Sessions(get)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+
+    let sessions = models.into_iter().map(|model| json!({
+        "id":           model.id,
+        "ip":           model.ip,
+        "user_agent":   model.user_agent,
+        "created_at":   model.created_at.and_utc().timestamp_millis(),
+        "last_seen":    model.last_seen.and_utc().timestamp_millis(),
+        "current":      model.id == jwt.session_id(),
+    })).collect::<Vec<_>>();
+    ServerResponse::ok(Some(json!({ "sessions": sessions })))
+}
+
+/// Signs a single device out: its refresh tokens stop working, its access tokens are
+/// revoked and an open websocket of that session is closed.
+async fn delete_session(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode();
+    let db = session::DB::from_state(&state);
+    match db.select_pk(id.clone()).await {
+        Ok(Some(model)) if model.user_id == user_pk as i32 && !model.revoked => {},
+        Ok(_) => return ServerResponse::fine(ServerResponseError::InvalidSessionId, None),
+        Err(e) => {
+            println!("[Sessions(delete)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    let revoked = async {
+        db.revoke(&id).await?;
+        refresh_token::DB::from_state(&state).revoke_family(&id).await?;
+        state.revocations.revoke_session(user_pk, &id).await?;
+        anyhow::Ok(())
+    };
+    if let Err(e) = revoked.await {
+        println!("[Sessions(delete)] Error: {}", e);
+        return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+    }
+
+    ws::disconnect(&state.users, user_pk, Some(&id)).await;
+
+    if id == jwt.session_id() {
+        token::clear_cookies(ServerResponse::ok(None))
+    } else {
+        ServerResponse::ok(None)
+    }
+}
@@ -0,0 +1,157 @@
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::post,
+    Router,
+};
+use axum_extra::{extract::cookie, headers::Cookie, TypedHeader};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration, Utc};
+use ring::digest::{digest, SHA256};
+use rand::RngCore;
+use serde_json::json;
+
+use crate::server::{
+    AppState, ServerResponse, ServerResponseError, JWT_EXPIRE_DURATION, REFRESH_EXPIRE_DURATION,
+};
+
+use crate::jwt::Jwt;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    refresh_token,
+    session,
+};
+use crate::id::{GeneralId, UserId};
+
+pub(crate) const ACCESS_COOKIE: &str = "token";
+pub(crate) const REFRESH_COOKIE: &str = "refresh_token";
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/token/refresh", post(post_refresh))
+        .with_state(app_state)
+}
+
+fn gen_refresh_token() -> String {
+    let mut bytes = [0u8; REFRESH_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Only the digest is stored, so a leaked table can't be replayed.
+pub(crate) fn hash_refresh_token(token: &str) -> String {
+    hex::encode(digest(&SHA256, token.as_bytes()))
+}
+
+pub(crate) fn token_cookie(name: &'static str, value: String, max_age_s: i64) -> cookie::Cookie<'static> {
+    cookie::Cookie::build((name, value))
+        .path("/")
+        .max_age(time::Duration::seconds(max_age_s))
+        .http_only(true)
+        .secure(false)
+        .build()
+}
+
+/// Expires both token cookies on the client.
+pub(crate) fn clear_cookies(res: ServerResponse) -> ServerResponse {
+    res.set_cookie(token_cookie(ACCESS_COOKIE, String::new(), 0))
+        .and_then(|res| res.set_cookie(token_cookie(REFRESH_COOKIE, String::new(), 0)))
+        .unwrap_or_else(|_| ServerResponse::inner_err(ServerResponseError::InternalUnknownError))
+}
+
+/// Mints an access JWT plus a refresh token for `user_pk` and sets both cookies.
+/// The refresh token family shares its id with the session it belongs to.
+pub(crate) async fn grant(
+    state: &AppState,
+    user_pk: i32,
+    session_id: &str,
+) -> Result<ServerResponse, ServerResponseError> {
+    let user_id = UserId::from_decoded(user_pk as u32);
+    let jwt = Jwt::generate(user_id.encode() as usize, session_id, JWT_EXPIRE_DURATION, &state.jwt_keys)
+        .map_err(|_| ServerResponseError::InternalTokenGenError)?;
+
+    let refresh = gen_refresh_token();
+    let expire_at = Utc::now().naive_utc() + Duration::seconds(REFRESH_EXPIRE_DURATION);
+    refresh_token::DB::from_state(state)
+        .issue(user_pk, session_id, hash_refresh_token(&refresh), expire_at)
+        .await
+        .map_err(|e| {
+            println!("[Token] Failed to issue refresh token: {}", e);
+            ServerResponseError::InternalDatabaseError
+        })?;
+
+    ServerResponse::ok(Some(json!({ "expires_in": JWT_EXPIRE_DURATION })))
+        .set_cookie(token_cookie(ACCESS_COOKIE, jwt, JWT_EXPIRE_DURATION))
+        .and_then(|res| res.set_cookie(token_cookie(REFRESH_COOKIE, refresh, REFRESH_EXPIRE_DURATION)))
+        .map_err(|_| ServerResponseError::InternalTokenGenError)
+}
+
+async fn post_refresh(
+    State(state): State<AppState>,
+    cookie: Option<TypedHeader<Cookie>>,
+) -> impl IntoResponse {
+    let Some(token) = cookie.as_ref().and_then(|TypedHeader(c)| c.get(REFRESH_COOKIE)) else {
+        return ServerResponse::fine(ServerResponseError::InvalidRefreshToken, None);
+    };
+
+    let db = refresh_token::DB::from_state(&state);
+    let model = match db.select_hash(&hash_refresh_token(token)).await {
+        Ok(Some(model)) => model,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::InvalidRefreshToken, None),
+        Err(e) => {
+            println!("[Refresh(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+
+    if model.revoked || model.expire_at <= Utc::now().naive_utc() {
+        return ServerResponse::fine(ServerResponseError::InvalidRefreshToken, None);
+    }
+    // a session revoked on its own must not come back through its refresh tokens
+    match session::DB::from_state(&state).select_pk(model.family_id.clone()).await {
+        Ok(Some(session)) if !session.revoked => {},
+        Ok(_) => return ServerResponse::fine(ServerResponseError::InvalidRefreshToken, None),
+        Err(e) => {
+            println!("[Refresh(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    // A token that was already rotated is being replayed, the whole family is compromised.
+    let fresh = if model.used { Ok(false) } else { db.mark_used(model.id).await };
+    match fresh {
+        Ok(true) => {},
+        Ok(false) => {
+            println!("[Refresh(post)] Reuse detected, revoking family {}", model.family_id);
+            if let Err(e) = db.revoke_family(&model.family_id).await {
+                println!("[Refresh(post)] Error: {}", e);
+                return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+            }
+            return ServerResponse::fine(ServerResponseError::ReusedRefreshToken, None);
+        },
+        Err(e) => {
+            println!("[Refresh(post)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    if let Err(e) = session::DB::from_state(&state).touch(&model.family_id).await {
+        println!("[Refresh(post)] Failed to touch session: {}", e);
+    }
+
+    grant(&state, model.user_id, &model.family_id)
+        .await
+        .unwrap_or_else(ServerResponse::inner_err)
+}
+
+
+#[test]
+fn refresh_token_test() {
+    let token = gen_refresh_token();
+    assert_eq!(URL_SAFE_NO_PAD.decode(&token).unwrap().len(), REFRESH_TOKEN_BYTES);
+    assert_ne!(token, gen_refresh_token());
+    assert_eq!(hash_refresh_token(&token).len(), 64);
+    assert_eq!(hash_refresh_token(&token), hash_refresh_token(&token));
+}
@@ -1,9 +1,10 @@
 use crate::jwt::Jwt;
 use crate::server::AppState;
 use crate::uuid::UUID;
-use axum::extract::Query;
+use axum::extract::{Query, State};
 use axum::routing::get;
 use axum::Router;
+use nanoid::nanoid;
 use serde::Deserialize;
 
 pub(crate) fn route(state: AppState) -> Router<AppState> {
@@ -16,8 +17,8 @@ struct GenJwtQuery {
     expr:   Option<i64>,
 }
 
-async fn new_jwt(Query(query): Query<GenJwtQuery>) -> String {
+async fn new_jwt(State(state): State<AppState>, Query(query): Query<GenJwtQuery>) -> String {
     let expr = query.expr.unwrap_or(3600);
     let id = query.id.unwrap_or(UUID::new().into());
-    Jwt::generate(id, expr).unwrap()
+    Jwt::generate(id, &nanoid!(), expr, &state.jwt_keys).unwrap()
 }
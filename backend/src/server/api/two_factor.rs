@@ -0,0 +1,310 @@
+use std::time::Duration;
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use dashmap::DashMap;
+use nanoid::nanoid;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::Instant;
+
+use crate::server::{AppState, ServerResponse, ServerResponseError};
+use crate::server::rate_limit::RateLimitLayer;
+
+use crate::jwt::Jwt;
+use crate::server::api::login;
+use crate::sql::{
+    BasicCRUD,
+    DataBase,
+    recovery_code,
+    user,
+    user_totp,
+};
+use crate::id::{GeneralId, UserId};
+use crate::totp;
+
+const TWO_FACTOR_TICKET_TTL: Duration = Duration::from_secs(5 * 60);
+const TWO_FACTOR_MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Deserialize)]
+struct CodeParams {
+    code:       Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeParams {
+    ticket:     Option<String>,
+    code:       Option<String>,
+}
+
+/// A login that passed the password check and waits for its second factor.
+#[derive(Debug)]
+struct PendingLogin {
+    user_pk:    i32,
+    ip:         String,
+    user_agent: Option<String>,
+    attempts:   u32,
+    expire_at:  Instant,
+}
+
+/// Logins waiting for a TOTP or recovery code, keyed by the ticket handed to the client.
+#[derive(Debug)]
+pub(crate) struct TwoFactorSession {
+    pending:        DashMap<String, PendingLogin>,
+    ticket_ttl:     Duration,
+    max_attempts:   u32,
+}
+
+impl Default for TwoFactorSession {
+    fn default() -> Self {
+        Self::new(TWO_FACTOR_TICKET_TTL, TWO_FACTOR_MAX_ATTEMPTS)
+    }
+}
+
+impl TwoFactorSession {
+    pub fn new(ticket_ttl: Duration, max_attempts: u32) -> Self {
+        Self {
+            pending: DashMap::new(),
+            ticket_ttl,
+            max_attempts,
+        }
+    }
+
+    pub fn start(&self, user_pk: i32, ip: String, user_agent: Option<String>) -> String {
+        let now = Instant::now();
+        self.pending.retain(|_, p| p.expire_at > now);
+
+        let ticket = nanoid!(32);
+        self.pending.insert(ticket.clone(), PendingLogin {
+            user_pk,
+            ip,
+            user_agent,
+            attempts: 0,
+            expire_at: now + self.ticket_ttl,
+        });
+        ticket
+    }
+
+    /// The user a live ticket belongs to.
+    fn user_of(&self, ticket: &str) -> Option<i32> {
+        let pending = self.pending.get(ticket)?;
+        if pending.expire_at <= Instant::now() {
+            drop(pending);
+            self.pending.remove(ticket);
+            return None;
+        }
+        Some(pending.user_pk)
+    }
+
+    /// Counts a wrong code, the ticket is dropped once it runs out of attempts.
+    fn fail(&self, ticket: &str) {
+        let exhausted = self.pending.get_mut(ticket).is_some_and(|mut p| {
+            p.attempts += 1;
+            p.attempts >= self.max_attempts
+        });
+        if exhausted {
+            self.pending.remove(ticket);
+        }
+    }
+
+    fn finish(&self, ticket: &str) -> Option<PendingLogin> {
+        self.pending.remove(ticket).map(|(_, p)| p)
+    }
+}
+
+pub(crate) fn route(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/login/2fa", post(post_exchange).layer(RateLimitLayer::new(app_state.auth_limiter.clone())))
+        .route("/2fa/enroll", post(post_enroll))
+        .route("/2fa/confirm", post(post_confirm))
+        .route("/2fa/disable", post(post_disable))
+        .with_state(app_state)
+}
+
+/// Whether `user_pk` has to pass a second factor after the password.
+pub(crate) async fn is_enabled(state: &AppState, user_pk: i32) -> anyhow::Result<bool> {
+    let model = user_totp::DB::from_state(state).select_user(user_pk).await?;
+    Ok(model.is_some_and(|model| model.enabled))
+}
+
+/// Accepts either a TOTP code or an unused recovery code, spending whichever matched.
+async fn check_code(state: &AppState, model: &user_totp::Model, code: &str) -> anyhow::Result<bool> {
+    let now = Utc::now().timestamp() as u64;
+    if let Some(step) = totp::verify(&model.secret, code, now, model.last_step as u64) {
+        return user_totp::DB::from_state(state).advance_step(model.user_id, step as i64).await;
+    }
+    if !model.enabled {
+        return Ok(false);
+    }
+    recovery_code::DB::from_state(state)
+        .consume(model.user_id, &totp::hash_recovery_code(code))
+        .await
+}
+
+async fn post_exchange(
+    State(state): State<AppState>,
+    Json(params): Json<ExchangeParams>,
+) -> impl IntoResponse {
+    let (Some(ticket), Some(code)) = (params.ticket, params.code) else {
+        return ServerResponse::fine(ServerResponseError::InvalidTwoFactorParams, None);
+    };
+    let logins = &state.two_factor_logins;
+    let Some(user_pk) = logins.user_of(&ticket) else {
+        return ServerResponse::fine(ServerResponseError::ExpiredTwoFactorTicket, None);
+    };
+
+    let checked = async {
+        match user_totp::DB::from_state(&state).select_user(user_pk).await? {
+            Some(model) if model.enabled => check_code(&state, &model, &code).await,
+            _ => Ok(false),
+        }
+    };
+    match checked.await {
+        Ok(true) => {},
+        Ok(false) => {
+            logins.fail(&ticket);
+            return ServerResponse::fine(ServerResponseError::InvalidTwoFactorCode, None);
+        },
+        Err(e) => {
+            println!("[2FA(exchange)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    }
+
+    // Another request may have used the same ticket in the meantime.
+    let Some(pending) = logins.finish(&ticket) else {
+        return ServerResponse::fine(ServerResponseError::ExpiredTwoFactorTicket, None);
+    };
+    login::open_session(&state, pending.user_pk, pending.ip, pending.user_agent).await
+}
+
+async fn post_enroll(
+    jwt: Jwt,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode() as i32;
+    let enrolled = async {
+        if is_enabled(&state, user_pk).await? {
+            return Ok(None);
+        }
+        let Some(user_model) = user::DB::from_state(&state).select_pk(user_pk).await? else {
+            return Ok(None);
+        };
+        let secret = totp::gen_secret();
+        user_totp::DB::from_state(&state).enroll(user_pk, secret.clone()).await?;
+        anyhow::Ok(Some((totp::otpauth_uri(&secret, &user_model.email), secret)))
+    };
+    match enrolled.await {
+        Ok(Some((uri, secret))) => ServerResponse::ok(Some(json!({ "secret": secret, "uri": uri }))),
+        Ok(None) => ServerResponse::fine(ServerResponseError::EnabledTwoFactor, None),
+        Err(e) => {
+            println!("[2FA(enroll)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+/// Turns 2FA on once the app proves it holds the secret, and hands out the recovery codes.
+async fn post_confirm(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Json(params): Json<CodeParams>,
+) -> impl IntoResponse {
+    let Some(code) = params.code else {
+        return ServerResponse::fine(ServerResponseError::InvalidTwoFactorParams, None);
+    };
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode() as i32;
+    let db = user_totp::DB::from_state(&state);
+    let model = match db.select_user(user_pk).await {
+        Ok(Some(model)) if model.enabled
+            => return ServerResponse::fine(ServerResponseError::EnabledTwoFactor, None),
+        Ok(Some(model)) => model,
+        Ok(None) => return ServerResponse::fine(ServerResponseError::DisabledTwoFactor, None),
+        Err(e) => {
+            println!("[2FA(confirm)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+
+    let codes = totp::gen_recovery_codes();
+    let confirmed = async {
+        if !check_code(&state, &model, &code).await? {
+            return Ok(false);
+        }
+        let hashes = codes.iter().map(|code| totp::hash_recovery_code(code)).collect();
+        recovery_code::DB::from_state(&state).replace(user_pk, hashes).await?;
+        db.enable(user_pk).await?;
+        anyhow::Ok(true)
+    };
+    match confirmed.await {
+        Ok(true) => ServerResponse::ok(Some(json!({ "recovery_codes": codes }))),
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidTwoFactorCode, None),
+        Err(e) => {
+            println!("[2FA(confirm)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+async fn post_disable(
+    jwt: Jwt,
+    State(state): State<AppState>,
+    Json(params): Json<CodeParams>,
+) -> impl IntoResponse {
+    let Some(code) = params.code else {
+        return ServerResponse::fine(ServerResponseError::InvalidTwoFactorParams, None);
+    };
+    let user_pk = UserId::from_encoded(jwt.user_id() as u32).decode() as i32;
+    let db = user_totp::DB::from_state(&state);
+    let model = match db.select_user(user_pk).await {
+        Ok(Some(model)) if model.enabled => model,
+        Ok(_) => return ServerResponse::fine(ServerResponseError::DisabledTwoFactor, None),
+        Err(e) => {
+            println!("[2FA(disable)] Error: {}", e);
+            return ServerResponse::inner_err(ServerResponseError::InternalDatabaseError);
+        }
+    };
+
+    let disabled = async {
+        if !check_code(&state, &model, &code).await? {
+            return Ok(false);
+        }
+        db.delete_user(user_pk).await?;
+        recovery_code::DB::from_state(&state).delete_user(user_pk).await?;
+        anyhow::Ok(true)
+    };
+    match disabled.await {
+        Ok(true) => ServerResponse::ok(None),
+        Ok(false) => ServerResponse::fine(ServerResponseError::InvalidTwoFactorCode, None),
+        Err(e) => {
+            println!("[2FA(disable)] Error: {}", e);
+            ServerResponse::inner_err(ServerResponseError::InternalDatabaseError)
+        }
+    }
+}
+
+
+#[test]
+fn two_factor_session_test() {
+    let logins = TwoFactorSession::new(Duration::from_secs(60), 2);
+    let ticket = logins.start(114514, "127.0.0.1".to_string(), None);
+    assert_eq!(logins.user_of(&ticket), Some(114514));
+    assert_eq!(logins.user_of("unknown"), None);
+
+    logins.fail(&ticket);
+    assert_eq!(logins.user_of(&ticket), Some(114514));
+    logins.fail(&ticket);
+    assert_eq!(logins.user_of(&ticket), None);
+
+    let ticket = logins.start(114514, "127.0.0.1".to_string(), None);
+    assert_eq!(logins.finish(&ticket).map(|p| p.user_pk), Some(114514));
+    assert!(logins.finish(&ticket).is_none());
+
+    let expired = TwoFactorSession::new(Duration::ZERO, 2);
+    let ticket = expired.start(114514, "127.0.0.1".to_string(), None);
+    assert_eq!(expired.user_of(&ticket), None);
+}
@@ -1,4 +1,6 @@
 mod api;
+mod permission;
+mod rate_limit;
 mod websocket;
 
 use std::fmt::Display;
@@ -28,10 +30,16 @@ use axum_extra::{
 use dashmap::DashMap;
 use sea_orm::DatabaseConnection;
 use tokio::sync::broadcast::Sender;
-use api::{login, public, register, tools};
-use websocket::{ws, WsClient};
-use crate::email::Email;
-use crate::jwt::{Jwt, JwtError};
+use api::{category, invite, login, lone, member, message, reaction, public, register, reset, role, room, session, token, tools, two_factor};
+use api::register::RegisterSession;
+use api::two_factor::TwoFactorSession;
+pub use api::lone::LoneConfig;
+use permission::Permissions;
+use rate_limit::RateLimiter;
+use websocket::{ws, WsClient, WsTickets};
+use crate::email::Mailer;
+use crate::jwt::{Jwt, JwtError, JwtKeys};
+use crate::revocation::RevocationList;
 use crate::sql::{
     user::DB,
     DataBase,
@@ -39,20 +47,84 @@ use crate::sql::{
 
 const FRONTEND_DIR: &'static str = "../../frontend";
 const JWT_EXPIRE_DURATION: i64 = 3600;
+const REFRESH_EXPIRE_DURATION: i64 = 30 * 24 * 3600;
 
+/// The discriminant is the errcode clients see, so a variant keeps its number for good
+/// and new ones take the next free number.
 #[derive(Debug, Copy, Clone)]
 enum ServerResponseError {
-    SUCCESS,
-    NullLoginParams,
-    IllegalLoginParams,
-    InvalidLoginParams,
-
-    InvalidRegisterParams,
-    ExistRegisterEmail,
-
-    InternalTokenGenError,
-    InternalDatabaseError,
-    InternalUnknownError,
+    SUCCESS                 = 0,
+    NullLoginParams         = 1,
+    IllegalLoginParams      = 2,
+    InvalidLoginParams      = 3,
+
+    InvalidRegisterParams   = 4,
+    ExistRegisterEmail      = 5,
+    PendingRegisterEmail    = 54,
+
+    InvalidVerifyParams     = 10,
+    MismatchVerifyCode      = 11,
+    ExpiredVerifyCode       = 12,
+    TooManyVerifyAttempts   = 13,
+    FrequentVerifyResend    = 14,
+
+    InvalidRefreshToken     = 15,
+    ReusedRefreshToken      = 16,
+
+    InvalidSessionId        = 17,
+
+    InvalidResetParams      = 18,
+    InvalidResetToken       = 19,
+
+    RequiredTwoFactor       = 20,
+    InvalidTwoFactorParams  = 21,
+    InvalidTwoFactorCode    = 22,
+    ExpiredTwoFactorTicket  = 23,
+    EnabledTwoFactor        = 24,
+    DisabledTwoFactor       = 25,
+
+    FrequentRequests        = 26,
+    LockedLoginEmail        = 27,
+    OversizedRequestBody    = 55,
+
+    InvalidLoneParams       = 28,
+    InvalidLoneId           = 29,
+    DeniedLonePermission    = 30,
+    InvalidLoneMember       = 31,
+    ExceededLoneLimit       = 32,
+
+    InvalidInviteParams     = 33,
+    InvalidInviteCode       = 34,
+    ExpiredInviteCode       = 35,
+
+    RequiredLoneTransfer    = 36,
+    InvalidBanParams        = 37,
+    InvalidBanId            = 38,
+    BannedFromLone          = 39,
+
+    InvalidRoleParams       = 40,
+    InvalidRoleId           = 41,
+    DeniedRoleHierarchy     = 42,
+
+    InvalidRoomParams       = 43,
+    InvalidRoomType         = 44,
+    InvalidRoomId           = 45,
+
+    InvalidCategoryParams   = 46,
+    InvalidCategoryId       = 47,
+
+    InvalidMessageParams    = 48,
+    InvalidMessageId        = 49,
+    DeniedMessageAuthor     = 50,
+
+    InvalidReactionParams   = 51,
+    InvalidReactionId       = 52,
+    ExceededReactionLimit   = 53,
+
+    InternalTokenGenError   = 6,
+    InternalDatabaseError   = 7,
+    InternalEmailError      = 9,
+    InternalUnknownError    = 8,
 }
 impl ServerResponseError {
     fn code(&self) -> u32 {
@@ -68,9 +140,70 @@ impl ServerResponseError {
             // -------------------------------register------------------------------ //
             ServerResponseError::InvalidRegisterParams  =>   "Invalid register params",
             ServerResponseError::ExistRegisterEmail     =>      "Email already exists",
+            ServerResponseError::PendingRegisterEmail   =>  "Email awaits verification",
+            // --------------------------------verify------------------------------- //
+            ServerResponseError::InvalidVerifyParams    =>     "Invalid verify params",
+            ServerResponseError::MismatchVerifyCode     => "Invalid verification code",
+            ServerResponseError::ExpiredVerifyCode      => "Verification code expired",
+            ServerResponseError::TooManyVerifyAttempts  =>   "Too many wrong attempts",
+            ServerResponseError::FrequentVerifyResend   =>     "Resend too frequently",
+            // --------------------------------token-------------------------------- //
+            ServerResponseError::InvalidRefreshToken    =>     "Invalid refresh token",
+            ServerResponseError::ReusedRefreshToken     =>  "Refresh token was reused",
+            // -------------------------------session------------------------------- //
+            ServerResponseError::InvalidSessionId       =>         "Session not found",
+            // --------------------------------reset-------------------------------- //
+            ServerResponseError::InvalidResetParams     =>      "Invalid reset params",
+            ServerResponseError::InvalidResetToken      =>       "Invalid reset token",
+            // ---------------------------------2fa--------------------------------- //
+            ServerResponseError::RequiredTwoFactor      =>         "2FA code required",
+            ServerResponseError::InvalidTwoFactorParams =>        "Invalid 2FA params",
+            ServerResponseError::InvalidTwoFactorCode   =>          "Invalid 2FA code",
+            ServerResponseError::ExpiredTwoFactorTicket =>         "2FA login expired",
+            ServerResponseError::EnabledTwoFactor       =>       "2FA already enabled",
+            ServerResponseError::DisabledTwoFactor      =>           "2FA not enabled",
+            // ------------------------------rate-limit----------------------------- //
+            ServerResponseError::FrequentRequests       =>         "Too many requests",
+            ServerResponseError::LockedLoginEmail       =>   "Account locked for now",
+            ServerResponseError::OversizedRequestBody   =>    "Request body too large",
+            // ---------------------------------lone-------------------------------- //
+            ServerResponseError::InvalidLoneParams      =>       "Invalid lone params",
+            ServerResponseError::InvalidLoneId          =>            "Lone not found",
+            ServerResponseError::DeniedLonePermission   =>         "Permission denied",
+            ServerResponseError::InvalidLoneMember      =>          "Member not found",
+            ServerResponseError::ExceededLoneLimit      =>     "Too many lones owned",
+            // --------------------------------invite------------------------------- //
+            ServerResponseError::InvalidInviteParams    =>     "Invalid invite params",
+            ServerResponseError::InvalidInviteCode      =>          "Invite not found",
+            ServerResponseError::ExpiredInviteCode      =>        "Invite has expired",
+            // --------------------------------member------------------------------- //
+            ServerResponseError::RequiredLoneTransfer   =>  "Transfer ownership first",
+            ServerResponseError::InvalidBanParams       =>        "Invalid ban params",
+            ServerResponseError::InvalidBanId           =>             "Ban not found",
+            ServerResponseError::BannedFromLone         =>     "Banned from this lone",
+            // ---------------------------------role-------------------------------- //
+            ServerResponseError::InvalidRoleParams      =>       "Invalid role params",
+            ServerResponseError::InvalidRoleId          =>            "Role not found",
+            ServerResponseError::DeniedRoleHierarchy    =>      "Role ranks too high",
+            // ---------------------------------room-------------------------------- //
+            ServerResponseError::InvalidRoomParams      =>       "Invalid room params",
+            ServerResponseError::InvalidRoomType        =>         "Invalid room type",
+            ServerResponseError::InvalidRoomId          =>            "Room not found",
+            // -------------------------------category------------------------------ //
+            ServerResponseError::InvalidCategoryParams  =>   "Invalid category params",
+            ServerResponseError::InvalidCategoryId      =>        "Category not found",
+            // -------------------------------message------------------------------- //
+            ServerResponseError::InvalidMessageParams   =>    "Invalid message params",
+            ServerResponseError::InvalidMessageId       =>         "Message not found",
+            ServerResponseError::DeniedMessageAuthor    =>  "Only the author may edit",
+            // -------------------------------reaction------------------------------ //
+            ServerResponseError::InvalidReactionParams  =>          "Invalid reaction",
+            ServerResponseError::InvalidReactionId      =>        "Reaction not found",
+            ServerResponseError::ExceededReactionLimit  =>        "Too many reactions",
             // -----------------------------general-error--------------------------- //
             ServerResponseError::InternalTokenGenError  =>            "Internal error",
             ServerResponseError::InternalDatabaseError  =>            "Internal error",
+            ServerResponseError::InternalEmailError     =>            "Internal error",
             ServerResponseError::InternalUnknownError   =>    "Internal unknown error",
         }
     }
@@ -131,7 +264,7 @@ impl ServerResponse {
 
     fn set_cookie(mut self, cookie: cookie::Cookie) -> Result<Self> {
         self.headers
-            .insert(header::SET_COOKIE, cookie.to_string().parse()?);
+            .append(header::SET_COOKIE, cookie.to_string().parse()?);
         Ok(self)
     }
 
@@ -194,28 +327,63 @@ pub async fn fs_read(path: &str) -> Result<String> {
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub db_conn: DatabaseConnection,
-    pub users: Arc<DashMap<u32, WsClient>>
+    pub users: Arc<DashMap<u32, WsClient>>,
+    pub mailer: Arc<dyn Mailer>,
+    pub(crate) register_sessions: Arc<RegisterSession>,
+    pub(crate) two_factor_logins: Arc<TwoFactorSession>,
+    pub(crate) auth_limiter: Arc<RateLimiter>,
+    pub(crate) ws_tickets: Arc<WsTickets>,
+    pub(crate) permissions: Arc<Permissions>,
+    pub lone_config: LoneConfig,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub revocations: Arc<RevocationList>,
 }
 
 impl AppState {
-    pub fn new(db_conn: DatabaseConnection) -> Self {
+    pub fn new(db_conn: DatabaseConnection, mailer: Arc<dyn Mailer>, jwt_keys: Arc<JwtKeys>) -> Self {
+        let revocations = Arc::new(RevocationList::new(db_conn.clone(), JWT_EXPIRE_DURATION));
         Self { 
             db_conn,
             users: Arc::new(DashMap::new()),
+            mailer,
+            register_sessions: Arc::new(RegisterSession::default()),
+            two_factor_logins: Arc::new(TwoFactorSession::default()),
+            auth_limiter: Arc::new(RateLimiter::default()),
+            ws_tickets: Arc::new(WsTickets::default()),
+            permissions: Arc::new(Permissions::default()),
+            lone_config: LoneConfig::default(),
+            jwt_keys,
+            revocations,
         }
     }
+
+    pub fn with_lone_config(mut self, lone_config: LoneConfig) -> Self {
+        self.lone_config = lone_config;
+        self
+    }
 }
 
 fn is_valid_email(email: &str) -> bool {
     regex::Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.(com|asia)$").unwrap().is_match(email)
 }
 
-pub fn route(db_conn: DatabaseConnection) -> Router {
-    let state = AppState::new(db_conn);
+pub fn route(state: AppState) -> Router {
 
     let login = login::route(state.clone());
     let public = public::route(state.clone());
     let register = register::route(state.clone());
+    let token = token::route(state.clone());
+    let session = session::route(state.clone());
+    let reset = reset::route(state.clone());
+    let two_factor = two_factor::route(state.clone());
+    let lone = lone::route(state.clone());
+    let invite = invite::route(state.clone());
+    let member = member::route(state.clone());
+    let role = role::route(state.clone());
+    let room = room::route(state.clone());
+    let category = category::route(state.clone());
+    let message = message::route(state.clone());
+    let reaction = reaction::route(state.clone());
     let websocket = ws::route(state.clone());
     let tools = tools::route(state.clone());
 
@@ -233,6 +401,18 @@ pub fn route(db_conn: DatabaseConnection) -> Router {
         public
             .merge(login)
             .merge(register)
+            .merge(token)
+            .merge(session)
+            .merge(reset)
+            .merge(two_factor)
+            .merge(lone)
+            .merge(invite)
+            .merge(member)
+            .merge(role)
+            .merge(room)
+            .merge(category)
+            .merge(message)
+            .merge(reaction)
             .merge(websocket)
             .nest("/tools", tools)
             .route("/chat", get(chat))
@@ -243,6 +423,18 @@ pub fn route(db_conn: DatabaseConnection) -> Router {
         Router::new()
             .nest("/", login)
             .nest("/", register)
+            .nest("/", token)
+            .nest("/", session)
+            .nest("/", reset)
+            .nest("/", two_factor)
+            .nest("/", lone)
+            .nest("/", invite)
+            .nest("/", member)
+            .nest("/", role)
+            .nest("/", room)
+            .nest("/", category)
+            .nest("/", message)
+            .nest("/", reaction)
             .nest("/", public)
             .nest("/", websocket)
             .route("/chat", get(chat))
@@ -265,3 +457,16 @@ async fn handler_404() -> Html<String> {
 // async fn handler_404() -> Html<&'static str> {
 //     Html::from("<html><body><h1>404 Not Found :(</h1></body></html>")
 // }
+
+
+#[test]
+fn errcode_test() {
+    // codes clients already know about
+    assert_eq!(ServerResponseError::SUCCESS.code(), 0);
+    assert_eq!(ServerResponseError::ExistRegisterEmail.code(), 5);
+    assert_eq!(ServerResponseError::InternalTokenGenError.code(), 6);
+    assert_eq!(ServerResponseError::InternalDatabaseError.code(), 7);
+    assert_eq!(ServerResponseError::InternalUnknownError.code(), 8);
+    assert_eq!(ServerResponseError::InternalEmailError.code(), 9);
+    assert_eq!(ServerResponseError::InvalidVerifyParams.code(), 10);
+}
@@ -0,0 +1,238 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use sea_orm::DatabaseConnection;
+
+use crate::entities::lone_role_info::RolePrivilege;
+use crate::sql::{category_identity, lone, lone_user, room, room_identity, BasicCRUD, DataBase};
+
+/// Entries kept before the cache starts over, it refills on demand.
+const MAX_CACHED: usize = 100_000;
+
+/// Allow and deny masks a room or category identity puts on top of the lone roles.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Override {
+    pub allow:  RolePrivilege,
+    pub deny:   RolePrivilege,
+}
+
+impl From<&room_identity::Model> for Override {
+    fn from(model: &room_identity::Model) -> Self {
+        Self {
+            allow:  RolePrivilege::from(model.allow),
+            deny:   RolePrivilege::from(model.deny),
+        }
+    }
+}
+
+impl From<&category_identity::Model> for Override {
+    fn from(model: &category_identity::Model) -> Self {
+        Self {
+            allow:  RolePrivilege::from(model.allow),
+            deny:   RolePrivilege::from(model.deny),
+        }
+    }
+}
+
+/// The overrides one level (a category or a room) holds for a member.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Layer {
+    pub everyone:   Option<Override>,
+    pub own:        Vec<Override>,
+}
+
+impl Layer {
+    /// Splits identities into the "@everyone" one and the member's own.
+    fn from_identities<'a>(identities: impl Iterator<Item = (&'a str, Override)>) -> Self {
+        identities.fold(Self::default(), |mut layer, (name, o)| {
+            if name == lone::EVERYONE_ROLE {
+                layer.everyone = Some(o);
+            } else {
+                layer.own.push(o);
+            }
+            layer
+        })
+    }
+}
+
+/// Combines what a member gets from their lone roles with the overrides of a room.
+///
+/// Layers apply from the outermost in, so a room's overrides win over its category's.
+/// Within a layer the "@everyone" override is applied before the member's own ones, each one
+/// takes away its deny mask and then adds its allow mask. Owners and administrators get
+/// everything, and overrides can't hand out administrator.
+pub(crate) fn resolve(is_owner: bool, roles: &[RolePrivilege], layers: &[Layer]) -> RolePrivilege {
+    let base = roles.iter().fold(RolePrivilege::empty(), |acc, role| acc | *role);
+    if is_owner || base.contains(RolePrivilege::ADMINISTRATOR) {
+        return RolePrivilege::all();
+    }
+    let apply = |mut privilege: RolePrivilege, o: &Override| {
+        privilege.revoke(o.deny);
+        privilege.grant(o.allow - RolePrivilege::ADMINISTRATOR);
+        privilege
+    };
+    layers.iter().fold(base, |privilege, layer| {
+        let privilege = layer.everyone.iter().fold(privilege, apply);
+        let own = layer.own.iter().fold(Override::default(), |acc, o| Override {
+            allow:  acc.allow | o.allow,
+            deny:   acc.deny | o.deny,
+        });
+        apply(privilege, &own)
+    })
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+struct Key {
+    lone_id:    i32,
+    user_id:    i32,
+    room_id:    Option<i32>,
+}
+
+/// Resolves and caches effective privileges. Non-members resolve to no privileges at all.
+///
+/// Anything that changes roles, identities, memberships or the owner of a lone has to
+/// invalidate it. Each lone has a generation that invalidation bumps, so a result computed
+/// from data read before the change never makes it into the cache.
+#[derive(Debug, Default)]
+pub(crate) struct Permissions {
+    cache:          DashMap<Key, RolePrivilege>,
+    generations:    DashMap<i32, u64>,
+    next_gen:       AtomicU64,
+}
+
+impl Permissions {
+    fn generation(&self, lone_id: i32) -> u64 {
+        self.generations.get(&lone_id).map(|g| *g).unwrap_or(0)
+    }
+
+    fn store(&self, key: Key, generation: u64, privilege: RolePrivilege) {
+        if self.generation(key.lone_id) != generation {
+            return;
+        }
+        if self.cache.len() >= MAX_CACHED {
+            self.cache.clear();
+        }
+        self.cache.insert(key, privilege);
+    }
+
+    /// Privileges of the user across the lone, without any room overrides.
+    pub async fn in_lone(
+        &self, conn: &DatabaseConnection, lone: &lone::Model, user_id: i32
+    ) -> Result<RolePrivilege> {
+        self.resolve(conn, lone, None, user_id).await
+    }
+
+    /// Privileges of the user in one room of the lone.
+    pub async fn in_room(
+        &self, conn: &DatabaseConnection, lone: &lone::Model, room_id: i32, user_id: i32
+    ) -> Result<RolePrivilege> {
+        self.resolve(conn, lone, Some(room_id), user_id).await
+    }
+
+    async fn resolve(
+        &self, conn: &DatabaseConnection, lone: &lone::Model, room_id: Option<i32>, user_id: i32
+    ) -> Result<RolePrivilege> {
+        let key = Key { lone_id: lone.id, user_id, room_id };
+        if let Some(privilege) = self.cache.get(&key) {
+            return Ok(*privilege);
+        }
+        let generation = self.generation(lone.id);
+
+        let roles = lone_user::DB::from_conn(conn.clone())
+            .select_privileges(lone.id, user_id).await?;
+        // no role, not a member
+        let privilege = if roles.is_empty() {
+            RolePrivilege::empty()
+        } else {
+            let layers = match room_id {
+                None => vec![],
+                Some(room_id) => Self::room_layers(conn, room_id, user_id).await?,
+            };
+            resolve(lone.owner_id == user_id, &roles, &layers)
+        };
+
+        self.store(key, generation, privilege);
+        Ok(privilege)
+    }
+
+    /// The category layer, if the room has a category, then the room layer.
+    async fn room_layers(conn: &DatabaseConnection, room_id: i32, user_id: i32) -> Result<Vec<Layer>> {
+        let mut layers = vec![];
+        let category_id = room::DB::from_conn(conn.clone()).select_pk(room_id).await?
+            .and_then(|room| room.category_id);
+        if let Some(category_id) = category_id {
+            let models = category_identity::DB::from_conn(conn.clone())
+                .select_overrides(category_id, user_id).await?;
+            layers.push(Layer::from_identities(
+                models.iter().map(|model| (model.name.as_str(), Override::from(model)))
+            ));
+        }
+        let models = room_identity::DB::from_conn(conn.clone())
+            .select_overrides(room_id, user_id).await?;
+        layers.push(Layer::from_identities(
+            models.iter().map(|model| (model.name.as_str(), Override::from(model)))
+        ));
+        Ok(layers)
+    }
+
+    /// Drops everything cached for the lone, e.g. after a role or room identity changed.
+    pub fn invalidate_lone(&self, lone_id: i32) {
+        let generation = self.next_gen.fetch_add(1, Ordering::Relaxed) + 1;
+        self.generations.insert(lone_id, generation);
+        self.cache.retain(|key, _| key.lone_id != lone_id);
+    }
+
+    /// Drops what is cached for one member, e.g. after they joined or left.
+    pub fn invalidate_member(&self, lone_id: i32, user_id: i32) {
+        let generation = self.next_gen.fetch_add(1, Ordering::Relaxed) + 1;
+        self.generations.insert(lone_id, generation);
+        self.cache.retain(|key, _| key.lone_id != lone_id || key.user_id != user_id);
+    }
+}
+
+
+#[test]
+fn permission_resolve_test() {
+    use RolePrivilege as P;
+
+    let room = |everyone: Option<Override>, own: &[Override]| Layer { everyone, own: own.to_vec() };
+    let everyone = P::everyone();
+    assert_eq!(resolve(false, &[everyone], &[]), everyone);
+    assert_eq!(resolve(true, &[everyone], &[]), P::all());
+    assert_eq!(resolve(false, &[everyone, P::ADMINISTRATOR], &[]), P::all());
+    assert_eq!(resolve(false, &[], &[]), P::empty());
+
+    // a private room: hidden from @everyone, shown to one identity
+    let hidden = Override { allow: P::empty(), deny: P::VIEW_ROOM };
+    let staff = Override { allow: P::VIEW_ROOM | P::MANAGE_MESSAGES, deny: P::empty() };
+    assert!(!resolve(false, &[everyone], &[room(Some(hidden), &[])]).contains(P::VIEW_ROOM));
+    let privilege = resolve(false, &[everyone], &[room(Some(hidden), &[staff])]);
+    assert!(privilege.contains(P::VIEW_ROOM | P::MANAGE_MESSAGES | P::SEND_MESSAGES));
+
+    // the member's own identities win over @everyone, and deny before allow
+    let muted = Override { allow: P::empty(), deny: P::SEND_MESSAGES };
+    let privilege = resolve(false, &[everyone], &[room(Some(staff), &[muted])]);
+    assert!(!privilege.contains(P::SEND_MESSAGES));
+    let both = Override { allow: P::SEND_MESSAGES, deny: P::SEND_MESSAGES };
+    assert!(resolve(false, &[everyone], &[room(None, &[both])]).contains(P::SEND_MESSAGES));
+
+    // overrides can't make administrators
+    let admin = Override { allow: P::ADMINISTRATOR, deny: P::empty() };
+    assert!(!resolve(false, &[everyone], &[room(None, &[admin])]).contains(P::ADMINISTRATOR));
+
+    // a hidden category hides its rooms, unless the room shows itself again
+    let category = room(Some(hidden), &[]);
+    assert!(!resolve(false, &[everyone], &[category.clone(), room(None, &[])]).contains(P::VIEW_ROOM));
+    let shown = Override { allow: P::VIEW_ROOM, deny: P::empty() };
+    assert!(resolve(false, &[everyone], &[category, room(Some(shown), &[])]).contains(P::VIEW_ROOM));
+
+    let permissions = Permissions::default();
+    let key = Key { lone_id: 1, user_id: 2, room_id: None };
+    permissions.store(key, 0, everyone);
+    let stale = permissions.generation(1);
+    permissions.invalidate_member(1, 2);
+    assert!(permissions.cache.get(&key).is_none());
+    permissions.store(key, stale, everyone);
+    assert!(permissions.cache.get(&key).is_none());
+}
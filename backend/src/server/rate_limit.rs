@@ -0,0 +1,332 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+use crate::server::{ServerResponse, ServerResponseError};
+
+// Per client IP: bursts of 20, then one request every 3 seconds.
+const IP_BUCKET: Bucket = Bucket { capacity: 20, refill: Duration::from_secs(3) };
+// Per target email: bursts of 10, then one request every 30 seconds.
+const EMAIL_BUCKET: Bucket = Bucket { capacity: 10, refill: Duration::from_secs(30) };
+const LOCKOUT_THRESHOLD: u32 = 5;
+const LOCKOUT_DURATION: Duration = Duration::from_secs(15 * 60);
+/// Bodies of rate limited routes are small JSON objects, anything larger is refused.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Bucket {
+    capacity:   u32,
+    /// time it takes to earn one token back
+    refill:     Duration,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens:     f64,
+    updated:    Instant,
+}
+
+impl TokenBucket {
+    fn full(bucket: Bucket, now: Instant) -> Self {
+        Self { tokens: bucket.capacity as f64, updated: now }
+    }
+
+    fn refill(&mut self, bucket: Bucket, now: Instant) {
+        let earned = now.duration_since(self.updated).as_secs_f64() / bucket.refill.as_secs_f64();
+        self.tokens = (self.tokens + earned).min(bucket.capacity as f64);
+        self.updated = now;
+    }
+
+    /// Takes one token, or tells how long until one is available.
+    fn take(&mut self, bucket: Bucket, now: Instant) -> Result<(), Duration> {
+        self.refill(bucket, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(bucket.refill.mul_f64(1.0 - self.tokens))
+        }
+    }
+
+    fn is_full(&self, bucket: Bucket, now: Instant) -> bool {
+        let earned = now.duration_since(self.updated).as_secs_f64() / bucket.refill.as_secs_f64();
+        self.tokens + earned >= bucket.capacity as f64
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count:          u32,
+    locked_until:   Option<Instant>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Limited {
+    /// Out of tokens, retry after the duration.
+    Throttled(Duration),
+    /// The email is locked for the client after too many failed logins from it.
+    Locked(Duration),
+}
+
+impl Limited {
+    fn retry_after(&self) -> Duration {
+        match self {
+            Limited::Throttled(wait) | Limited::Locked(wait) => *wait,
+        }
+    }
+}
+
+impl From<Limited> for ServerResponse {
+    fn from(limited: Limited) -> Self {
+        let error = match limited {
+            Limited::Throttled(_) => ServerResponseError::FrequentRequests,
+            Limited::Locked(_) => ServerResponseError::LockedLoginEmail,
+        };
+        let retry_after = limited.retry_after().as_secs().max(1);
+        let res = ServerResponse::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            error,
+            Some(json!({ "retry_after": retry_after })),
+        );
+        res.set_header(header::RETRY_AFTER, &retry_after.to_string())
+            .unwrap_or_else(|_| ServerResponse::inner_err(ServerResponseError::InternalUnknownError))
+    }
+}
+
+/// Token buckets per client IP and per target email, plus the failed-login lockout.
+///
+/// Failures count per client IP and email, so someone guessing from elsewhere can't lock
+/// the owner of the email out.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    ips:        DashMap<IpAddr, TokenBucket>,
+    emails:     DashMap<String, TokenBucket>,
+    failures:   DashMap<(Option<IpAddr>, String), Failures>,
+    ip_bucket:          Bucket,
+    email_bucket:       Bucket,
+    lockout_threshold:  u32,
+    lockout_duration:   Duration,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(IP_BUCKET, EMAIL_BUCKET, LOCKOUT_THRESHOLD, LOCKOUT_DURATION)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(ip_bucket: Bucket, email_bucket: Bucket, lockout_threshold: u32, lockout_duration: Duration) -> Self {
+        Self {
+            ips:        DashMap::new(),
+            emails:     DashMap::new(),
+            failures:   DashMap::new(),
+            ip_bucket,
+            email_bucket,
+            lockout_threshold,
+            lockout_duration,
+        }
+    }
+
+    fn normalize(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    fn locked_for(&self, ip: Option<IpAddr>, email: &str, now: Instant) -> Option<Duration> {
+        let failures = self.failures.get(&(ip, email.to_string()))?;
+        failures.locked_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Spends a token from every bucket the request falls into.
+    pub fn check(&self, ip: Option<IpAddr>, email: Option<&str>) -> Result<(), Limited> {
+        let now = Instant::now();
+        let email = email.map(Self::normalize);
+        if let Some(wait) = email.as_deref().and_then(|email| self.locked_for(ip, email, now)) {
+            return Err(Limited::Locked(wait));
+        }
+
+        if let Some(ip) = ip {
+            self.ips.entry(ip)
+                .or_insert_with(|| TokenBucket::full(self.ip_bucket, now))
+                .take(self.ip_bucket, now)
+                .map_err(Limited::Throttled)?;
+        }
+        if let Some(email) = email {
+            self.emails.entry(email)
+                .or_insert_with(|| TokenBucket::full(self.email_bucket, now))
+                .take(self.email_bucket, now)
+                .map_err(Limited::Throttled)?;
+        }
+        Ok(())
+    }
+
+    /// Counts a failed login from `ip`, the email gets locked for that IP once it reaches
+    /// the threshold. Unknown emails are counted as well so a lockout doesn't tell them apart.
+    pub fn record_failure(&self, ip: Option<IpAddr>, email: &str) {
+        let now = Instant::now();
+        let mut failures = self.failures.entry((ip, Self::normalize(email)))
+            .or_insert(Failures { count: 0, locked_until: None });
+        if failures.locked_until.is_some_and(|until| until <= now) {
+            failures.locked_until = None;
+        }
+        failures.count += 1;
+        if failures.count >= self.lockout_threshold {
+            failures.count = 0;
+            failures.locked_until = Some(now + self.lockout_duration);
+        }
+    }
+
+    pub fn record_success(&self, ip: Option<IpAddr>, email: &str) {
+        self.failures.remove(&(ip, Self::normalize(email)));
+    }
+
+    /// Forgets buckets that refilled completely and lockouts that are over.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.ips.retain(|_, b| !b.is_full(self.ip_bucket, now));
+        self.emails.retain(|_, b| !b.is_full(self.email_bucket, now));
+        self.failures.retain(|_, f| f.locked_until.is_none_or(|until| until > now));
+    }
+
+    pub fn spawn_pruner(self: Arc<Self>, period: Duration) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(period).await;
+                self.prune();
+            }
+        });
+    }
+}
+
+/// Tower layer putting a [`RateLimiter`] in front of a route.
+///
+/// The client IP comes from `ConnectInfo<SocketAddr>`, the email from the `email` field
+/// of a JSON body, if there is one.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit { inner, limiter: self.limiter.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimit<S> {
+    inner:      S,
+    limiter:    Arc<RateLimiter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmailField {
+    email: Option<String>,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // The clone may not be ready, keep the one `poll_ready` was called on.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let ip = parts.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip());
+            let Ok(bytes) = to_bytes(body, MAX_BODY_SIZE).await else {
+                return Ok(ServerResponse::new(
+                    StatusCode::PAYLOAD_TOO_LARGE, ServerResponseError::OversizedRequestBody, None,
+                ).into_response());
+            };
+            let email = serde_json::from_slice::<EmailField>(&bytes)
+                .ok()
+                .and_then(|field| field.email);
+
+            if let Err(limited) = limiter.check(ip, email.as_deref()) {
+                println!("[RateLimit] Rejected {:?} / {:?}: {:?}", ip, email, limited);
+                return Ok(ServerResponse::from(limited).into_response());
+            }
+            inner.call(Request::from_parts(parts, Body::from(bytes))).await
+        })
+    }
+}
+
+
+#[tokio::test]
+async fn rate_limit_test() {
+    use axum::{routing::post, Router};
+    use tower::ServiceExt;
+
+    let bucket = Bucket { capacity: 2, refill: Duration::from_secs(60) };
+    let limiter = Arc::new(RateLimiter::new(IP_BUCKET, bucket, 3, Duration::from_secs(60)));
+    let app = Router::new()
+        .route("/login", post(|| async { "ok" }).layer(RateLimitLayer::new(limiter.clone())));
+    let login = |email: &str| Request::post("/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "email": email }).to_string()))
+        .unwrap();
+
+    for _ in 0..2 {
+        let res = app.clone().oneshot(login("a@b.com")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    let res = app.clone().oneshot(login("A@b.com")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(res.headers().get(header::RETRY_AFTER).is_some());
+    // other emails have their own bucket
+    let res = app.clone().oneshot(login("c@d.com")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let (ip, other_ip) = (Some([10, 0, 0, 1].into()), Some([10, 0, 0, 2].into()));
+    for _ in 0..3 {
+        limiter.record_failure(ip, "e@f.com");
+    }
+    assert!(matches!(limiter.check(ip, Some("e@f.com")), Err(Limited::Locked(_))));
+    // the owner logging in from elsewhere isn't locked out
+    assert!(limiter.check(other_ip, Some("e@f.com")).is_ok());
+    limiter.record_success(ip, "g@h.com");
+    assert!(limiter.check(ip, Some("g@h.com")).is_ok());
+
+    let res = app.clone().oneshot(Request::post("/login")
+        .body(Body::from(vec![b' '; MAX_BODY_SIZE + 1]))
+        .unwrap()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -11,8 +11,10 @@ use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
 use crate::server::websocket::error::Error;
 
-#[derive(Debug)]
+/// Cheap to clone, clones share the connection.
+#[derive(Debug, Clone)]
 pub struct WsClient {
+    session_id: String,
     alive_cnt:  Arc<AtomicBool>,
     sender:     mpsc::Sender<Message>,
     rx_queue:   Arc<Mutex<mpsc::Receiver<Message>>>,
@@ -21,11 +23,13 @@ pub struct WsClient {
 
 
 impl WsClient {
-    pub fn new(tx: mpsc::Sender<Message>,
+    pub fn new(session_id: String,
+               tx: mpsc::Sender<Message>,
                rx: mpsc::Receiver<Message>,
                task: JoinHandle<i32>
     ) -> Self {
         WsClient {
+            session_id,
             sender:     tx,
             alive_cnt:  Arc::new(AtomicBool::new(true)),
             rx_queue:   Arc::new(Mutex::new(rx)),
@@ -33,6 +37,15 @@ impl WsClient {
         }
     }
     
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Whether both are handles to the same connection.
+    pub fn same_as(&self, other: &WsClient) -> bool {
+        Arc::ptr_eq(&self.alive_cnt, &other.alive_cnt)
+    }
+
     pub fn get_sender(&self) -> mpsc::Sender<Message> {
         self.sender.clone()
     }
@@ -116,6 +129,11 @@ impl WsClient {
             Ok(())
         }
     }
+
+    /// Asks the client to close, the send task ends once the frame is out.
+    pub async fn close(&self) -> Result<(), Error> {
+        self.send(Message::Close(None)).await
+    }
 }
 
 // pub struct WsConnections(DashMap<PkRoomId, DashMap<PkUserId, Connection>>);
\ No newline at end of file
@@ -62,14 +62,90 @@ pub enum Event {
         message:    String,
     },
     Chat {
+        /// the message id
         event_id:   u32,
         content: ChatContent,
         quote:   Option<u32>,
-    }
+        /// milliseconds
+        edited_at:  Option<i64>,
+    },
+    ChatEdit {
+        event_id:   u32,
+        content:    ChatContent,
+        edited_at:  i64,
+    },
+    ChatDelete {
+        event_id:   u32,
+    },
+    ReactionAdd {
+        event_id:   u32,
+        user_id:    u32,
+        emoji:      String,
+        count:      u64,
+    },
+    ReactionRemove {
+        event_id:   u32,
+        user_id:    u32,
+        emoji:      String,
+        count:      u64,
+    },
+    MemberJoin {
+        user_id:    u32,
+    },
+    MemberLeave {
+        user_id:    u32,
+    },
+    MemberKick {
+        user_id:        u32,
+        moderator_id:   u32,
+    },
+    MemberBan {
+        user_id:        u32,
+        moderator_id:   u32,
+        reason:         Option<String>,
+        expire_at:      Option<i64>,
+    },
+    MemberUnban {
+        user_id:        u32,
+        moderator_id:   u32,
+    },
+    RoomCreate {
+        room:       serde_json::Value,
+    },
+    RoomUpdate {
+        room:       serde_json::Value,
+    },
+    RoomDelete {
+        room_id:    u32,
+    },
+    RoomReorder {
+        /// the list that was reordered, none for the rooms without a category
+        category_id:    Option<u32>,
+        room_ids:       Vec<u32>,
+    },
+    RoomMove {
+        room_id:        u32,
+        category_id:    Option<u32>,
+        position:       i32,
+    },
+    CategoryCreate {
+        category:   serde_json::Value,
+    },
+    CategoryUpdate {
+        category:   serde_json::Value,
+    },
+    CategoryDelete {
+        category_id:    u32,
+    },
+    CategoryReorder {
+        category_ids:   Vec<u32>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Payload {
+    /// sequence number of the signal carrying the payload, 0 for stored messages returned by
+    /// the history, which never were signals. Messages are told apart by their `event_id`.
     pub(crate) id:         u32,
     pub(crate) author:     Author,
     pub(crate) scope:      Scope,
@@ -2,5 +2,10 @@ pub mod ws;
 mod conn;
 mod event;
 mod error;
+mod notify;
+mod ticket;
 
-pub use conn::{WsClient};
\ No newline at end of file
+pub use conn::{WsClient};
+pub(crate) use ticket::WsTickets;
+pub(crate) use event::{Author, ChatContent, ChatText, Event, Payload, Scope};
+pub(crate) use notify::{notify_lone, notify_room, notify_users, notify_visible_rooms, room_viewers};
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::entities::lone_role_info::RolePrivilege;
+use crate::id::{GeneralId, LoneId, RoomId};
+use crate::server::AppState;
+use crate::sql::{lone, lone_user, DataBase};
+use super::event::{Author, Event, Payload, Scope};
+use super::ws::WsSignal;
+
+/// Sequence number shared by every server-sent signal.
+static SIGNAL_SN: AtomicU32 = AtomicU32::new(1);
+
+/// Sends a system event to every connected member of the lone, and to `extra` who may
+/// not be in it anymore, e.g. someone who was just kicked.
+/// Delivery is best effort, members that aren't connected just miss it.
+pub(crate) async fn notify_lone(state: &AppState, lone_id: i32, extra: Option<i32>, event: Event) {
+    let mut users = match lone_user::DB::from_state(state).select_members(lone_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            println!("[Notify] Error: {}", e);
+            return;
+        }
+    };
+    users.extend(extra.filter(|user_id| !users.contains(user_id)));
+
+    let scope = Scope::Lone { lone_id: LoneId::from_decoded(lone_id as u32).encode() };
+    deliver(state, &users, Author::System, scope, event).await;
+}
+
+/// Members of the lone with an open websocket.
+async fn connected_members(state: &AppState, lone_id: i32) -> Vec<i32> {
+    match lone_user::DB::from_state(state).select_members(lone_id).await {
+        Ok(members) => members.into_iter()
+            .filter(|user_id| state.users.contains_key(&(*user_id as u32)))
+            .collect(),
+        Err(e) => {
+            println!("[Notify] Error: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// Those of `users` who can see the room.
+async fn viewers(state: &AppState, lone: &lone::Model, room_id: i32, users: &[i32]) -> Vec<i32> {
+    let mut viewers = vec![];
+    for user_id in users {
+        match state.permissions.in_room(&state.db_conn, lone, room_id, *user_id).await {
+            Ok(held) if held.allows(RolePrivilege::VIEW_ROOM) => viewers.push(*user_id),
+            Ok(_) => {},
+            Err(e) => println!("[Notify] Error: {}", e),
+        }
+    }
+    viewers
+}
+
+/// Connected members of the lone who can see the room. Read it before a change that takes
+/// the room's overrides away, deleting it for one.
+pub(crate) async fn room_viewers(state: &AppState, lone: &lone::Model, room_id: i32) -> Vec<i32> {
+    let members = connected_members(state, lone.id).await;
+    viewers(state, lone, room_id, &members).await
+}
+
+/// Sends an event posted in a room to every connected member of the lone who can see the room.
+pub(crate) async fn notify_room(state: &AppState, lone: &lone::Model, room_id: i32, author: Author, event: Event) {
+    let users = room_viewers(state, lone, room_id).await;
+    let scope = Scope::Room {
+        lone_id:    LoneId::from_decoded(lone.id as u32).encode(),
+        room_id:    RoomId::from_decoded(room_id as u32).encode(),
+    };
+    deliver(state, &users, author, scope, event).await;
+}
+
+/// Sends a system event of the lone to `users` only, such as the viewers of a room.
+pub(crate) async fn notify_users(state: &AppState, lone_id: i32, users: &[i32], event: Event) {
+    let scope = Scope::Lone { lone_id: LoneId::from_decoded(lone_id as u32).encode() };
+    deliver(state, users, Author::System, scope, event).await;
+}
+
+/// Tells every connected member of the lone about the rooms among `room_ids` they can see,
+/// in the same order. `event` builds each member's event from their encoded room ids,
+/// members who see none of the rooms get nothing.
+pub(crate) async fn notify_visible_rooms(
+    state: &AppState, lone: &lone::Model, room_ids: &[i32], event: impl Fn(Vec<u32>) -> Event
+) {
+    let members = connected_members(state, lone.id).await;
+    let mut visible = HashMap::<i32, Vec<u32>>::new();
+    for room_id in room_ids {
+        for user_id in viewers(state, lone, *room_id, &members).await {
+            visible.entry(user_id).or_default().push(RoomId::from_decoded(*room_id as u32).encode());
+        }
+    }
+    for (user_id, room_ids) in visible {
+        notify_users(state, lone.id, &[user_id], event(room_ids)).await;
+    }
+}
+
+async fn deliver(state: &AppState, users: &[i32], author: Author, scope: Scope, event: Event) {
+    let sn = SIGNAL_SN.fetch_add(1, Ordering::Relaxed);
+    let payload = Payload::new(sn, author, scope, event);
+
+    for user_id in users {
+        // Clone the sender out so the map isn't locked across the await.
+        let Some(sender) = state.users.get(&(*user_id as u32)).map(|client| client.get_sender()) else {
+            continue;
+        };
+        if let Err(e) = sender.send(WsSignal::new(sn, payload.clone()).into()).await {
+            println!("[Notify] Failed to reach user {}: {}", user_id, e);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+use dashmap::DashMap;
+use nanoid::nanoid;
+use tokio::time::Instant;
+
+use crate::jwt::Jwt;
+
+const WS_TICKET_TTL: Duration = Duration::from_secs(30);
+
+/// One-time tickets for the `/ws` handshake.
+///
+/// Browsers can't set headers on a websocket upgrade, so an authenticated client trades
+/// its token for a ticket first and passes it as `/ws?ticket=...`.
+#[derive(Debug)]
+pub(crate) struct WsTickets {
+    tickets:    DashMap<String, (Jwt, Instant)>,
+    ttl:        Duration,
+}
+
+impl Default for WsTickets {
+    fn default() -> Self {
+        Self::new(WS_TICKET_TTL)
+    }
+}
+
+impl WsTickets {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            tickets: DashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn issue(&self, jwt: Jwt) -> String {
+        let now = Instant::now();
+        self.tickets.retain(|_, (_, expire_at)| *expire_at > now);
+
+        let ticket = nanoid!(32);
+        self.tickets.insert(ticket.clone(), (jwt, now + self.ttl));
+        ticket
+    }
+
+    /// Spends a ticket, returning the token it was issued for.
+    pub fn redeem(&self, ticket: &str) -> Option<Jwt> {
+        let (_, (jwt, expire_at)) = self.tickets.remove(ticket)?;
+        (expire_at > Instant::now()).then_some(jwt)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+
+#[test]
+fn ws_ticket_test() {
+    use crate::jwt::test_keys;
+    use serde_json::json;
+
+    let keys = test_keys(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }]
+    }));
+    let jwt = || Jwt::parse_and_verify(&Jwt::generate(114514, "test", 60, &keys).unwrap(), &keys).unwrap();
+
+    let tickets = WsTickets::default();
+    let ticket = tickets.issue(jwt());
+    assert!(tickets.redeem("unknown").is_none());
+    assert_eq!(tickets.redeem(&ticket).map(|jwt| jwt.user_id()), Some(114514));
+    assert!(tickets.redeem(&ticket).is_none());
+
+    let expired = WsTickets::new(Duration::ZERO);
+    let ticket = expired.issue(jwt());
+    assert!(expired.redeem(&ticket).is_none());
+}
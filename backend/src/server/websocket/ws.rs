@@ -4,11 +4,12 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use anyhow::anyhow;
-use axum::extract::{ConnectInfo, State, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, FromRequestParts, Query, State, WebSocketUpgrade};
+use axum::http::request::Parts;
 use axum::extract::ws::{Message, Utf8Bytes, WebSocket};
-use axum::response::Response;
-use axum::{Router, ServiceExt};
-use axum::routing::get;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router, ServiceExt};
+use axum::routing::{get, post};
 use futures::{SinkExt, StreamExt};
 
 use dashmap::DashMap;
@@ -17,7 +18,7 @@ use serde_json::json;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
 use crate::id::{GeneralId, UserId};
-use crate::jwt::Jwt;
+use crate::jwt::{Jwt, JwtError};
 use crate::server::AppState;
 use crate::server::websocket::conn::WsClient;
 use crate::server::websocket::error::Error;
@@ -26,10 +27,21 @@ use super::event::{Author, ChatContent, Event, MediaMeta, Payload, Scope};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WsSignal {
     sn:         u32,
-    timestamp:  u32,
+    /// when the signal was sent, in milliseconds like every time the API returns
+    timestamp:  i64,
     payload:    Payload,
 }
 
+impl WsSignal {
+    pub fn new(sn: u32, payload: Payload) -> Self {
+        WsSignal {
+            sn,
+            timestamp:  chrono::Utc::now().timestamp_millis(),
+            payload,
+        }
+    }
+}
+
 impl Into<Message> for WsSignal {
     fn into(self) -> Message {
         let signal = serde_json::to_string(&self)
@@ -55,21 +67,59 @@ impl Into<Message> for WsSignal {
 pub(crate) fn route(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/ws", get(handler))
+        .route("/ws/ticket", post(post_ticket))
         .with_state(state)
 }
 
-async fn handler(
+#[derive(Debug, Deserialize)]
+struct TicketQuery {
+    ticket:     Option<String>,
+}
+
+async fn post_ticket(
     jwt: Jwt,
+    State(state): State<AppState>,
+) -> Json<serde_json::Value> {
+    let ticket = state.ws_tickets.issue(jwt);
+    Json(json!({ "ticket": ticket, "expires_in": state.ws_tickets.ttl().as_secs() }))
+}
+
+/// Authenticates the handshake with `?ticket=` if given, otherwise like any other request.
+async fn authenticate(
+    parts: &mut Parts,
+    state: &AppState,
+    query: TicketQuery,
+) -> Result<Jwt, JwtError> {
+    let Some(ticket) = query.ticket else {
+        return Jwt::from_request_parts(parts, state).await;
+    };
+    let jwt = state.ws_tickets.redeem(&ticket).ok_or(JwtError::InvalidToken)?;
+    // The token may have expired or been revoked since the ticket was issued.
+    jwt.verify(&state.jwt_keys)?;
+    if state.revocations.is_revoked(&jwt) {
+        return Err(JwtError::Revoked);
+    }
+    Ok(jwt)
+}
+
+async fn handler(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
+    Query(query): Query<TicketQuery>,
+    mut parts: Parts,
 ) -> Response {
+    let jwt = match authenticate(&mut parts, &state, query).await {
+        Ok(jwt) => jwt,
+        Err(e) => return e.into_response(),
+    };
     println!("{} connected.", addr);
     let pk_uid = UserId::from_encoded(jwt.user_id() as u32).decode();
-    ws.on_upgrade(move |socket| ws_handler(socket, pk_uid, state))
+    let session_id = jwt.session_id().to_string();
+    ws.on_upgrade(move |socket| ws_handler(socket, pk_uid, session_id, state))
 }
 
-async fn ws_handler(mut socket: WebSocket, pk_uid: u32, state: AppState) -> () {
+async fn ws_handler(mut socket: WebSocket, pk_uid: u32, session_id: String, state: AppState) -> () {
     println!("user_id: {}.", pk_uid);
     let (mut sender, mut receiver) = socket.split();
 
@@ -139,8 +189,8 @@ async fn ws_handler(mut socket: WebSocket, pk_uid: u32, state: AppState) -> () {
         res
     });
 
-    state.users.insert(pk_uid, WsClient::new(send_tx, recv_rx, ws_task));
-    state.users.get(&pk_uid).unwrap().task(30).await;
+    let client = WsClient::new(session_id, send_tx, recv_rx, ws_task);
+    serve(&state.users, pk_uid, client).await;
 
     return ;
 
@@ -185,14 +235,44 @@ async fn ws_handler(mut socket: WebSocket, pk_uid: u32, state: AppState) -> () {
     // }.await;
 }
 
+/// Registers the connection of `pk_uid` and runs it until it ends.
+///
+/// The map only holds a clone, no guard is kept across the await, otherwise removing
+/// the user from another task would block until the client went away by itself.
+async fn serve(users: &DashMap<u32, WsClient>, pk_uid: u32, client: WsClient) {
+    users.insert(pk_uid, client.clone());
+    client.task(30).await;
+    // a newer connection may have taken the slot meanwhile
+    users.remove_if(&pk_uid, |_, c| c.same_as(&client));
+}
+
+/// Closes the open websocket of `pk_uid`, only if it belongs to `session_id` when given.
+pub(crate) async fn disconnect(users: &DashMap<u32, WsClient>, pk_uid: u32, session_id: Option<&str>) {
+    let removed = users.remove_if(&pk_uid, |_, c| session_id.is_none_or(|id| c.session_id() == id));
+    if let Some((_, client)) = removed {
+        if let Err(e) = client.close().await {
+            println!("[WebSocket] Failed to close websocket of {}: {:?}", pk_uid, e);
+        }
+    }
+}
+
+
+#[cfg(test)]
+use crate::email::MockMailer;
+#[cfg(test)]
+use crate::jwt::{JwtConfig, JwtKeys};
 
 #[tokio::test]
 async fn test_main() -> Result<(), Error>{
     // start an axum server
-    let state = AppState {
-        db_conn: Default::default(),
-        users: Arc::new(DashMap::new()),
-    };
+    let jwt_keys: JwtConfig = serde_json::from_value(json!({
+        "keys": [{ "kid": "test", "secret": "test_secret_test_secret_test_secret" }]
+    })).unwrap();
+    let state = AppState::new(
+        Default::default(),
+        Arc::new(MockMailer::default()),
+        Arc::new(JwtKeys::from_config(jwt_keys).unwrap()),
+    );
     let app = Router::new().merge(route(state.clone())).with_state(state.clone());
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
@@ -248,7 +328,7 @@ async fn test_event() {
         id: 114,
         author: Author::User { id: 114514 },
         scope: Scope::Room { lone_id: 1919, room_id: 810 },
-        event: Event::Chat { event_id: 1111111, content: image, quote: Some(6666) },
+        event: Event::Chat { event_id: 1111111, content: image, quote: Some(6666), edited_at: None },
     };
     let event = WsSignal {
         sn: 6,
@@ -258,3 +338,40 @@ async fn test_event() {
     println!("{}", serde_json::to_string(&event).unwrap())
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn disconnect_test() {
+    let users = Arc::new(DashMap::<u32, WsClient>::new());
+    let (send_tx, mut send_rx) = mpsc::channel(32);
+    let (_recv_tx, recv_rx) = mpsc::channel(32);
+    // stands in for the socket: ends once the close frame was sent
+    let ws_task = tokio::spawn(async move {
+        while let Some(msg) = send_rx.recv().await {
+            if let Message::Close(_) = msg {
+                return 0;
+            }
+        }
+        1
+    });
+    let client = WsClient::new("session".to_string(), send_tx, recv_rx, ws_task);
+    let serving = tokio::spawn({
+        let users = users.clone();
+        async move { serve(&users, 1, client).await }
+    });
+    while !users.contains_key(&1) {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // another session's revocation leaves the socket alone
+    disconnect(&users, 1, Some("other")).await;
+    assert!(users.contains_key(&1));
+
+    // on its own task, a blocked removal would hold up that worker and not this one
+    let revoke = tokio::spawn({
+        let users = users.clone();
+        async move { disconnect(&users, 1, Some("session")).await }
+    });
+    let revoked = tokio::time::timeout(Duration::from_secs(5), revoke).await;
+    assert!(revoked.is_ok(), "revoking blocked on the open socket");
+    assert!(!users.contains_key(&1));
+    assert!(tokio::time::timeout(Duration::from_secs(5), serving).await.is_ok());
+}
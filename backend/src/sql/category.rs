@@ -0,0 +1,164 @@
+use crate::entities::prelude::RoomCategory;
+crate::database!(RoomCategory);
+
+use std::collections::HashSet;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, Order, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+use crate::entities::{lone_info, room_info};
+
+/// Serializes position changes within a lone.
+async fn lock_lone<C: ConnectionTrait>(conn: &C, lone_id: i32) -> Result<(), Error> {
+    lone_info::Entity::find_by_id(lone_id)
+        .lock_exclusive()
+        .one(conn).await?;
+    Ok(())
+}
+
+/// Rooms of a lone under one category, or under none, in display order.
+async fn select_rooms<C: ConnectionTrait>(
+    conn: &C, lone_id: i32, category_id: Option<i32>
+) -> Result<Vec<room_info::Model>, Error> {
+    let models = room_info::Entity::find()
+        .filter(room_info::Column::LoneId.eq(lone_id))
+        .filter(match category_id {
+            Some(category_id) => room_info::Column::CategoryId.eq(category_id),
+            None => room_info::Column::CategoryId.is_null(),
+        })
+        .order_by(room_info::Column::Position, Order::Asc)
+        .all(conn).await?;
+    Ok(models)
+}
+
+/// Numbers the rooms from 0 in the given order and puts them under the category.
+async fn renumber<C: ConnectionTrait>(
+    conn: &C, rooms: &[i32], category_id: Option<i32>
+) -> Result<(), Error> {
+    for (position, id) in rooms.iter().enumerate() {
+        room_info::ActiveModel {
+            id:             ActiveValue::Set(*id),
+            position:       ActiveValue::Set(position as i32),
+            category_id:    ActiveValue::Set(category_id),
+            ..Default::default()
+        }.update(conn).await?;
+    }
+    Ok(())
+}
+
+impl DB {
+    /// Categories of a lone in display order.
+    pub async fn select_lone(&self, lone_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::LoneId.eq(lone_id)],
+            Some((Column::Position, Order::Asc)),
+        ).await?;
+        Ok(models)
+    }
+
+    /// Creates a category at the end of the lone's list.
+    pub async fn create(&self, lone_id: i32, name: String) -> Result<Model, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let last = Entity::find()
+            .select_only()
+            .column_as(Column::Position.max(), "position")
+            .filter(Column::LoneId.eq(lone_id))
+            .into_tuple::<Option<i32>>()
+            .one(&txn).await?
+            .flatten();
+        let model = ActiveModel {
+            lone_id:    ActiveValue::Set(lone_id),
+            name:       ActiveValue::Set(name),
+            position:   ActiveValue::Set(last.map_or(0, |last| last + 1)),
+            ..Default::default()
+        }.insert(&txn).await?;
+        txn.commit().await?;
+        Ok(model)
+    }
+
+    pub async fn rename(&self, id: i32, name: String) -> Result<Model, Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id),
+            name:       ActiveValue::Set(name),
+            ..Default::default()
+        };
+        Ok(model.update(self.conn()).await?)
+    }
+
+    /// Deletes a category, its rooms go to the end of the uncategorized ones.
+    pub async fn remove(&self, lone_id: i32, id: i32) -> Result<(), Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let rooms = select_rooms(&txn, lone_id, None).await?.into_iter()
+            .chain(select_rooms(&txn, lone_id, Some(id)).await?)
+            .map(|room| room.id)
+            .collect::<Vec<_>>();
+        renumber(&txn, &rooms, None).await?;
+        Entity::delete_by_id(id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Puts the categories of a lone in the order of `category_ids`, which must name each one once.
+    /// Returns `None` if it doesn't.
+    pub async fn reorder(&self, lone_id: i32, category_ids: &[i32]) -> Result<Option<Vec<Model>>, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let current = Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .filter(Column::LoneId.eq(lone_id))
+            .into_tuple::<i32>()
+            .all(&txn).await?;
+        let wanted = category_ids.iter().copied().collect::<HashSet<_>>();
+        if wanted.len() != category_ids.len() || wanted != current.into_iter().collect() {
+            return Ok(None);
+        }
+        for (position, id) in category_ids.iter().enumerate() {
+            ActiveModel {
+                id:         ActiveValue::Set(*id),
+                position:   ActiveValue::Set(position as i32),
+                ..Default::default()
+            }.update(&txn).await?;
+        }
+        txn.commit().await?;
+        Ok(Some(self.select_lone(lone_id).await?))
+    }
+
+    /// Moves a room of the lone under `category_id` (or out of any category) at `position`,
+    /// renumbering the rooms it leaves and the ones it joins in the same transaction.
+    /// Returns `None` if the room or the category isn't part of the lone.
+    pub async fn move_room(
+        &self, lone_id: i32, room_id: i32, category_id: Option<i32>, position: usize
+    ) -> Result<Option<room_info::Model>, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let Some(room) = room_info::Entity::find_by_id(room_id).one(&txn).await?
+            .filter(|room| room.lone_id == lone_id) else {
+            return Ok(None);
+        };
+        if let Some(category_id) = category_id {
+            let found = Entity::find_by_id(category_id).one(&txn).await?
+                .is_some_and(|category| category.lone_id == lone_id);
+            if !found {
+                return Ok(None);
+            }
+        }
+
+        if room.category_id != category_id {
+            let left = select_rooms(&txn, lone_id, room.category_id).await?.into_iter()
+                .filter(|model| model.id != room.id)
+                .map(|model| model.id)
+                .collect::<Vec<_>>();
+            renumber(&txn, &left, room.category_id).await?;
+        }
+        let mut joined = select_rooms(&txn, lone_id, category_id).await?.into_iter()
+            .filter(|model| model.id != room.id)
+            .map(|model| model.id)
+            .collect::<Vec<_>>();
+        joined.insert(position.min(joined.len()), room.id);
+        renumber(&txn, &joined, category_id).await?;
+
+        let room = room_info::Entity::find_by_id(room_id).one(&txn).await?;
+        txn.commit().await?;
+        Ok(room)
+    }
+}
@@ -1,3 +1,127 @@
 use crate::entities::prelude::LoneInfo;
 crate::database!(LoneInfo);
 
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+use crate::entities::{assoc_lone_user, lone_role_info, user_info};
+use crate::entities::lone_role_info::RolePrivilege;
+use crate::sql::lone_user;
+
+pub const EVERYONE_ROLE: &str = "@everyone";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transfer {
+    Done(Model),
+    /// `from` doesn't own the lone (anymore).
+    NotOwner,
+    /// `to` isn't a member of the lone.
+    NotMember,
+    /// `to` already owns as many lones as allowed.
+    LimitReached,
+}
+
+/// Locks the user row so concurrent creates and transfers can't both slip under `max_owned`,
+/// then tells whether the user may own one more lone.
+async fn lock_owner_slot<C: ConnectionTrait>(conn: &C, user_id: i32, max_owned: u64) -> Result<bool, Error> {
+    user_info::Entity::find_by_id(user_id)
+        .lock_exclusive()
+        .one(conn).await?;
+    let owned = Entity::find()
+        .filter(Column::OwnerId.eq(user_id))
+        .count(conn).await?;
+    Ok(owned < max_owned)
+}
+
+/// Locks the lone row until the transaction ends, so membership changes of a lone
+/// (joining, bans) happen one at a time.
+pub async fn lock<C: ConnectionTrait>(conn: &C, lone_id: i32) -> Result<(), Error> {
+    Entity::find_by_id(lone_id)
+        .lock_exclusive()
+        .one(conn).await?;
+    Ok(())
+}
+
+impl DB {
+    /// Creates a lone together with its "@everyone" role and the owner's membership.
+    /// Returns `None` if the owner already owns `max_owned` lones.
+    pub async fn create(&self, owner_id: i32, name: String, max_owned: u64) -> Result<Option<Model>, Error> {
+        let txn = self.conn().begin().await?;
+        if !lock_owner_slot(&txn, owner_id, max_owned).await? {
+            return Ok(None);
+        }
+
+        let lone = ActiveModel {
+            name:       ActiveValue::Set(name),
+            owner_id:   ActiveValue::Set(owner_id),
+            ..Default::default()
+        }.insert(&txn).await?;
+
+        let everyone = lone_role_info::ActiveModel {
+            name:       ActiveValue::Set(EVERYONE_ROLE.to_string()),
+            lone_id:    ActiveValue::Set(lone.id),
+            privilege:  ActiveValue::Set(RolePrivilege::everyone().into()),
+            ..Default::default()
+        }.insert(&txn).await?;
+
+        assoc_lone_user::ActiveModel {
+            lone_id:    ActiveValue::Set(lone.id),
+            user_id:    ActiveValue::Set(owner_id),
+            role_id:    ActiveValue::Set(everyone.id),
+        }.insert(&txn).await?;
+
+        txn.commit().await?;
+        Ok(Some(lone))
+    }
+
+    /// Hands the lone from `from` to `to`, checking ownership, membership and the cap
+    /// in the same transaction as the update.
+    pub async fn transfer(&self, id: i32, from: i32, to: i32, max_owned: u64) -> Result<Transfer, Error> {
+        let txn = self.conn().begin().await?;
+        let lone = Entity::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn).await?;
+        let Some(lone) = lone.filter(|lone| lone.owner_id == from) else {
+            return Ok(Transfer::NotOwner);
+        };
+        if !lone_user::is_member(&txn, id, to).await? {
+            return Ok(Transfer::NotMember);
+        }
+        if !lock_owner_slot(&txn, to, max_owned).await? {
+            return Ok(Transfer::LimitReached);
+        }
+
+        let mut lone: ActiveModel = lone.into();
+        lone.owner_id = ActiveValue::Set(to);
+        let lone = lone.update(&txn).await?;
+        txn.commit().await?;
+        Ok(Transfer::Done(lone))
+    }
+
+    pub async fn rename(&self, id: i32, name: String) -> Result<Model, Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id),
+            name:       ActiveValue::Set(name),
+            ..Default::default()
+        };
+        Ok(model.update(self.conn()).await?)
+    }
+
+    /// Lones `user_id` is a member of, oldest first.
+    pub async fn select_member(&self, user_id: i32) -> Result<Vec<Model>, Error> {
+        let lone_ids = assoc_lone_user::Entity::find()
+            .select_only()
+            .column(assoc_lone_user::Column::LoneId)
+            .distinct()
+            .filter(assoc_lone_user::Column::UserId.eq(user_id))
+            .into_tuple::<i32>()
+            .all(self.conn()).await?;
+        let models = Entity::find()
+            .filter(Column::Id.is_in(lone_ids))
+            .order_by_asc(Column::CreatedAt)
+            .all(self.conn()).await?;
+        Ok(models)
+    }
+
+    pub async fn is_member(&self, id: i32, user_id: i32) -> Result<bool, Error> {
+        lone_user::is_member(self.conn(), id, user_id).await
+    }
+}
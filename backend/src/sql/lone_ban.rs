@@ -0,0 +1,67 @@
+use crate::entities::prelude::LoneBan;
+crate::database!(LoneBan);
+
+use chrono::NaiveDateTime;
+use sea_orm::{ActiveValue, ConnectionTrait, Order, TransactionTrait};
+use sea_orm::sea_query::OnConflict;
+use crate::sql::{lone, lone_user};
+
+pub async fn is_banned<C: ConnectionTrait>(
+    conn: &C, lone_id: i32, user_id: i32, now: NaiveDateTime
+) -> Result<bool, Error> {
+    let ban = Entity::find_by_id((lone_id, user_id)).one(conn).await?;
+    Ok(ban.is_some_and(|ban| is_active(&ban, now)))
+}
+
+pub fn is_active(ban: &Model, now: NaiveDateTime) -> bool {
+    ban.expire_at.is_none_or(|expire_at| expire_at > now)
+}
+
+impl DB {
+    /// Bans the user and removes them from the lone in one go. Banning again replaces
+    /// the previous ban. Returns whether the user was a member.
+    pub async fn ban(
+        &self,
+        lone_id: i32,
+        user_id: i32,
+        moderator_id: i32,
+        reason: Option<String>,
+        expire_at: Option<NaiveDateTime>,
+    ) -> Result<bool, Error> {
+        let txn = self.conn().begin().await?;
+        // same lock as joining, the user can't get back in while the ban is written
+        lone::lock(&txn, lone_id).await?;
+        let model = ActiveModel {
+            lone_id:        ActiveValue::Set(lone_id),
+            user_id:        ActiveValue::Set(user_id),
+            moderator_id:   ActiveValue::Set(Some(moderator_id)),
+            reason:         ActiveValue::Set(reason),
+            expire_at:      ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        Entity::insert(model)
+            .on_conflict(
+                OnConflict::columns([Column::LoneId, Column::UserId])
+                    .update_columns([Column::ModeratorId, Column::Reason, Column::ExpireAt])
+                    .to_owned()
+            )
+            .exec(&txn).await?;
+        let was_member = lone_user::remove(&txn, lone_id, user_id).await?;
+        txn.commit().await?;
+        Ok(was_member)
+    }
+
+    /// Returns whether there was a ban to lift.
+    pub async fn unban(&self, lone_id: i32, user_id: i32) -> Result<bool, Error> {
+        Ok(self.delete_pk((lone_id, user_id)).await?)
+    }
+
+    /// Bans of a lone that are still in effect, newest first.
+    pub async fn select_lone(&self, lone_id: i32, now: NaiveDateTime) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::LoneId.eq(lone_id)],
+            Some((Column::CreatedAt, Order::Desc)),
+        ).await?;
+        Ok(models.into_iter().filter(|ban| is_active(ban, now)).collect())
+    }
+}
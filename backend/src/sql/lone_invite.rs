@@ -0,0 +1,82 @@
+use crate::entities::prelude::LoneInvite;
+crate::database!(LoneInvite);
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{ActiveModelTrait, ActiveValue, Order, QuerySelect, TransactionTrait};
+use crate::sql::lone_user::{self, Join};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redeem {
+    Joined(Model),
+    /// The user was in the lone already, the invite isn't used up.
+    AlreadyMember(Model),
+    NotFound,
+    /// The user is banned from the lone.
+    Banned,
+    /// Past its expiry or out of uses.
+    Expired,
+}
+
+impl DB {
+    pub async fn create(
+        &self,
+        code: String,
+        lone_id: i32,
+        creator_id: i32,
+        room_id: Option<i32>,
+        max_uses: Option<i32>,
+        expire_at: Option<NaiveDateTime>,
+    ) -> Result<Model, Error> {
+        let model = ActiveModel {
+            code:       ActiveValue::Set(code),
+            lone_id:    ActiveValue::Set(lone_id),
+            creator_id: ActiveValue::Set(creator_id),
+            room_id:    ActiveValue::Set(room_id),
+            max_uses:   ActiveValue::Set(max_uses),
+            expire_at:  ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        Ok(model.insert(self.conn()).await?)
+    }
+
+    /// Invites of a lone, newest first.
+    pub async fn select_lone(&self, lone_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::LoneId.eq(lone_id)],
+            Some((Column::CreatedAt, Order::Desc)),
+        ).await?;
+        Ok(models)
+    }
+
+    /// Joins `user_id` to the invite's lone and counts the use, all in one transaction
+    /// so an invite can't be used more than `max_uses` times.
+    pub async fn redeem(&self, code: &str, user_id: i32) -> Result<Redeem, Error> {
+        let txn = self.conn().begin().await?;
+        let invite = Entity::find_by_id(code.to_string())
+            .lock_exclusive()
+            .one(&txn).await?;
+        let Some(invite) = invite else {
+            return Ok(Redeem::NotFound);
+        };
+        if !is_usable(&invite, Utc::now().naive_utc()) {
+            return Ok(Redeem::Expired);
+        }
+        match lone_user::join(&txn, invite.lone_id, user_id).await? {
+            Join::Joined => {},
+            Join::AlreadyMember => return Ok(Redeem::AlreadyMember(invite)),
+            Join::Banned => return Ok(Redeem::Banned),
+        }
+
+        let uses = invite.uses + 1;
+        let mut invite: ActiveModel = invite.into();
+        invite.uses = ActiveValue::Set(uses);
+        let invite = invite.update(&txn).await?;
+        txn.commit().await?;
+        Ok(Redeem::Joined(invite))
+    }
+}
+
+pub fn is_usable(invite: &Model, now: NaiveDateTime) -> bool {
+    invite.expire_at.is_none_or(|expire_at| expire_at > now)
+        && invite.max_uses.is_none_or(|max_uses| invite.uses < max_uses)
+}
@@ -1,18 +1,105 @@
-use sea_orm::{ActiveValue, DatabaseConnection, EntityTrait};
-use sea_orm::DbErr;
-use crate::entities::assoc_lone_user::ActiveModel;
 use crate::entities::prelude::AssocLoneUser;
-use crate::id::{GeneralId, LoneId, RoleId, UserId};
-
-async fn insert(
-    conn: &DatabaseConnection,
-    lone_id: LoneId, user_id: UserId, role_id: RoleId
-) -> Result<(), DbErr> {
-    let model = ActiveModel {
-        lone_id: ActiveValue::Set(lone_id.decode() as i32),
-        user_id: ActiveValue::Set(user_id.decode() as i32),
-        role_id: ActiveValue::Set(role_id.decode() as i32),
-    };
-    AssocLoneUser::insert(model).exec(conn).await?;
-    Ok(())
-}
\ No newline at end of file
+crate::database!(AssocLoneUser);
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, QueryFilter, QuerySelect};
+use crate::entities::lone_role_info::{self, RolePrivilege};
+use crate::sql::lone::EVERYONE_ROLE;
+use crate::sql::{lone, lone_ban};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Join {
+    Joined,
+    AlreadyMember,
+    Banned,
+}
+
+/// Adds `user_id` to the lone with the "@everyone" role, unless they're banned or in already.
+/// Holds the lone row so a ban can't land between the check and the insert, `conn` should be
+/// a transaction.
+pub async fn join<C: ConnectionTrait>(conn: &C, lone_id: i32, user_id: i32) -> Result<Join, Error> {
+    lone::lock(conn, lone_id).await?;
+    if is_member(conn, lone_id, user_id).await? {
+        return Ok(Join::AlreadyMember);
+    }
+    if lone_ban::is_banned(conn, lone_id, user_id, Utc::now().naive_utc()).await? {
+        return Ok(Join::Banned);
+    }
+    let everyone = lone_role_info::Entity::find()
+        .filter(lone_role_info::Column::LoneId.eq(lone_id))
+        .filter(lone_role_info::Column::Name.eq(EVERYONE_ROLE))
+        .one(conn).await?
+        .ok_or_else(|| anyhow::anyhow!("lone {} has no {} role", lone_id, EVERYONE_ROLE))?;
+    ActiveModel {
+        lone_id:    ActiveValue::Set(lone_id),
+        user_id:    ActiveValue::Set(user_id),
+        role_id:    ActiveValue::Set(everyone.id),
+    }.insert(conn).await?;
+    Ok(Join::Joined)
+}
+
+pub async fn is_member<C: ConnectionTrait>(conn: &C, lone_id: i32, user_id: i32) -> Result<bool, Error> {
+    let row = Entity::find()
+        .filter(Column::LoneId.eq(lone_id))
+        .filter(Column::UserId.eq(user_id))
+        .one(conn).await?;
+    Ok(row.is_some())
+}
+
+/// Drops every role row of the user in the lone. Returns whether they were a member.
+pub async fn remove<C: ConnectionTrait>(conn: &C, lone_id: i32, user_id: i32) -> Result<bool, Error> {
+    let res = Entity::delete_many()
+        .filter(Column::LoneId.eq(lone_id))
+        .filter(Column::UserId.eq(user_id))
+        .exec(conn).await?;
+    Ok(res.rows_affected > 0)
+}
+
+impl DB {
+    /// User ids of everyone in the lone.
+    pub async fn select_members(&self, lone_id: i32) -> Result<Vec<i32>, Error> {
+        let user_ids = Entity::find()
+            .select_only()
+            .column(Column::UserId)
+            .distinct()
+            .filter(Column::LoneId.eq(lone_id))
+            .into_tuple::<i32>()
+            .all(self.conn()).await?;
+        Ok(user_ids)
+    }
+
+    /// Privileges of every lone role the user holds.
+    pub async fn select_privileges(&self, lone_id: i32, user_id: i32) -> Result<Vec<RolePrivilege>, Error> {
+        let privileges = lone_role_info::Entity::find()
+            .inner_join(AssocLoneUser)
+            .select_only()
+            .column(lone_role_info::Column::Privilege)
+            .filter(Column::LoneId.eq(lone_id))
+            .filter(Column::UserId.eq(user_id))
+            .into_tuple::<i64>()
+            .all(self.conn()).await?;
+        Ok(privileges.into_iter().map(RolePrivilege::from).collect())
+    }
+
+    /// Gives the member a role. Returns whether they didn't have it yet.
+    pub async fn assign(&self, lone_id: i32, user_id: i32, role_id: i32) -> Result<bool, Error> {
+        if self.select_pk((lone_id, user_id, role_id)).await?.is_some() {
+            return Ok(false);
+        }
+        ActiveModel {
+            lone_id:    ActiveValue::Set(lone_id),
+            user_id:    ActiveValue::Set(user_id),
+            role_id:    ActiveValue::Set(role_id),
+        }.insert(self.conn()).await?;
+        Ok(true)
+    }
+
+    /// Takes a role from the member. Returns whether they had it.
+    pub async fn unassign(&self, lone_id: i32, user_id: i32, role_id: i32) -> Result<bool, Error> {
+        Ok(self.delete_pk((lone_id, user_id, role_id)).await?)
+    }
+
+    pub async fn leave(&self, lone_id: i32, user_id: i32) -> Result<bool, Error> {
+        remove(self.conn(), lone_id, user_id).await
+    }
+}
@@ -0,0 +1,196 @@
+use crate::entities::prelude::Message;
+crate::database!(Message);
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, Order, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+use serde_json::Value;
+use crate::sql::message_revision::{self, DELETE, EDIT};
+
+/// Where a message is posted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Target {
+    Lone(i32),
+    Room { lone_id: i32, room_id: i32 },
+    /// The conversation between the author and this user.
+    Private(i32),
+}
+
+impl Target {
+    pub fn of(model: &Model) -> Option<Self> {
+        match (model.lone_id, model.room_id, model.peer_id) {
+            (Some(lone_id), None, None) => Some(Target::Lone(lone_id)),
+            (Some(lone_id), Some(room_id), None) => Some(Target::Room { lone_id, room_id }),
+            (None, None, Some(peer_id)) => Some(Target::Private(peer_id)),
+            _ => None,
+        }
+    }
+
+    /// Whether `model` was posted where `author_id` posting here would end up.
+    /// Both sides of a private conversation share it.
+    pub fn holds(&self, author_id: Option<i32>, model: &Model) -> bool {
+        match (*self, Target::of(model)) {
+            (Target::Private(peer_id), Some(Target::Private(other))) => {
+                (model.author_id, other) == (author_id, peer_id)
+                    || (model.author_id, Some(other)) == (Some(peer_id), author_id)
+            },
+            (target, other) => Some(target) == other,
+        }
+    }
+}
+
+/// Where a page of history starts, relative to a message id.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Cursor {
+    Latest,
+    Before(i32),
+    After(i32),
+    /// The message itself and those right before and after it.
+    Around(i32),
+}
+
+/// Loads a message that isn't deleted, locked until the transaction ends.
+async fn lock_message<C: ConnectionTrait>(conn: &C, id: i32) -> Result<Option<Model>, Error> {
+    let model = Entity::find_by_id(id)
+        .filter(Column::DeletedAt.is_null())
+        .lock_exclusive()
+        .one(conn).await?;
+    Ok(model)
+}
+
+/// Keeps what the message says before `editor_id` changes it.
+async fn keep_revision<C: ConnectionTrait>(
+    conn: &C, model: &Model, editor_id: i32, action: &str
+) -> Result<(), Error> {
+    message_revision::ActiveModel {
+        message_id: ActiveValue::Set(model.id),
+        editor_id:  ActiveValue::Set(Some(editor_id)),
+        action:     ActiveValue::Set(action.to_string()),
+        content:    ActiveValue::Set(model.content.clone()),
+        ..Default::default()
+    }.insert(conn).await?;
+    Ok(())
+}
+
+impl DB {
+    /// Up to `limit` messages of the room at the cursor, oldest first.
+    pub async fn select_room(&self, room_id: i32, cursor: Cursor, limit: u64) -> Result<Vec<Model>, Error> {
+        let page = |filter: Option<sea_orm::sea_query::SimpleExpr>, order: Order, limit: u64| {
+            let mut query = Entity::find()
+                .filter(Column::RoomId.eq(room_id))
+                .filter(Column::DeletedAt.is_null());
+            if let Some(filter) = filter {
+                query = query.filter(filter);
+            }
+            query.order_by(Column::Id, order).limit(limit).all(self.conn())
+        };
+        let models = match cursor {
+            Cursor::Latest => page(None, Order::Desc, limit).await?.into_iter().rev().collect(),
+            Cursor::Before(id) => page(Some(Column::Id.lt(id)), Order::Desc, limit).await?.into_iter().rev().collect(),
+            Cursor::After(id) => page(Some(Column::Id.gt(id)), Order::Asc, limit).await?,
+            Cursor::Around(id) => {
+                let before = limit / 2;
+                let mut models = page(Some(Column::Id.lt(id)), Order::Desc, before).await?;
+                models.reverse();
+                models.extend(page(Some(Column::Id.gte(id)), Order::Asc, limit - before).await?);
+                models
+            },
+        };
+        Ok(models)
+    }
+
+    /// Stores a message, the database assigns its id and time.
+    /// No author means a system message. Returns `None` if `quote_id` is not a message
+    /// of the same place.
+    pub async fn create(
+        &self, author_id: Option<i32>, target: Target, content: Value, quote_id: Option<i32>
+    ) -> Result<Option<Model>, Error> {
+        if let Some(quote_id) = quote_id {
+            let quoted = self.select_pk(quote_id).await?;
+            let found = quoted
+                .is_some_and(|quoted| quoted.deleted_at.is_none() && target.holds(author_id, &quoted));
+            if !found {
+                return Ok(None);
+            }
+        }
+        let (lone_id, room_id, peer_id) = match target {
+            Target::Lone(lone_id) => (Some(lone_id), None, None),
+            Target::Room { lone_id, room_id } => (Some(lone_id), Some(room_id), None),
+            Target::Private(peer_id) => (None, None, Some(peer_id)),
+        };
+        let model = ActiveModel {
+            author_id:  ActiveValue::Set(author_id),
+            lone_id:    ActiveValue::Set(lone_id),
+            room_id:    ActiveValue::Set(room_id),
+            peer_id:    ActiveValue::Set(peer_id),
+            content:    ActiveValue::Set(content),
+            quote_id:   ActiveValue::Set(quote_id),
+            ..Default::default()
+        }.insert(self.conn()).await?;
+        Ok(Some(model))
+    }
+
+    /// Replaces the content of a message, keeping the old one as a revision.
+    /// Returns `None` if the message is gone.
+    pub async fn edit(&self, id: i32, editor_id: i32, content: Value) -> Result<Option<Model>, Error> {
+        let txn = self.conn().begin().await?;
+        let Some(model) = lock_message(&txn, id).await? else {
+            return Ok(None);
+        };
+        keep_revision(&txn, &model, editor_id, EDIT).await?;
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id),
+            content:    ActiveValue::Set(content),
+            edited_at:  ActiveValue::Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }.update(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(model))
+    }
+
+    /// Marks a message deleted, its content stays in the revisions.
+    /// Returns `false` if it was already gone.
+    pub async fn remove(&self, id: i32, moderator_id: i32) -> Result<bool, Error> {
+        let txn = self.conn().begin().await?;
+        let Some(model) = lock_message(&txn, id).await? else {
+            return Ok(false);
+        };
+        keep_revision(&txn, &model, moderator_id, DELETE).await?;
+        ActiveModel {
+            id:         ActiveValue::Set(id),
+            deleted_at: ActiveValue::Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }.update(&txn).await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+}
+
+
+#[test]
+fn message_target_test() {
+    let model = |author_id, lone_id, room_id, peer_id| Model {
+        id:         1,
+        author_id,
+        lone_id,
+        room_id,
+        peer_id,
+        content:    Value::Null,
+        quote_id:   None,
+        created_at: chrono::NaiveDateTime::default(),
+        edited_at:  None,
+        deleted_at: None,
+    };
+
+    let room = Target::Room { lone_id: 1, room_id: 2 };
+    assert_eq!(Target::of(&model(Some(3), Some(1), Some(2), None)), Some(room));
+    assert!(room.holds(Some(4), &model(Some(3), Some(1), Some(2), None)));
+    assert!(!room.holds(Some(4), &model(Some(3), Some(1), None, None)));
+    assert_eq!(Target::of(&model(Some(3), Some(1), None, Some(4))), None);
+
+    // 3 wrote to 4, both of them can quote it, nobody else
+    let sent = model(Some(3), None, None, Some(4));
+    assert!(Target::Private(4).holds(Some(3), &sent));
+    assert!(Target::Private(3).holds(Some(4), &sent));
+    assert!(!Target::Private(3).holds(Some(5), &sent));
+    assert!(!Target::Private(5).holds(Some(3), &sent));
+}
@@ -0,0 +1,129 @@
+use crate::entities::prelude::MessageReaction;
+crate::database!(MessageReaction);
+
+use std::collections::HashMap;
+use sea_orm::{ActiveModelTrait, ActiveValue, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+use crate::entities::message;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum React {
+    Added(u64),
+    AlreadyReacted,
+    NotFound,
+    LimitReached,
+}
+
+/// How many users reacted to a message with one emoji.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Count {
+    pub emoji:  String,
+    pub count:  u64,
+    /// whether the asking user is one of them
+    pub me:     bool,
+}
+
+impl DB {
+    /// Adds the user's reaction, a message takes at most `max_distinct` different emojis.
+    /// `Added` carries how many reacted with the emoji now.
+    pub async fn react(&self, message_id: i32, user_id: i32, emoji: &str, max_distinct: u64) -> Result<React, Error> {
+        let txn = self.conn().begin().await?;
+        // the message row serializes reactions to it, so the limit holds
+        let found = message::Entity::find_by_id(message_id)
+            .filter(message::Column::DeletedAt.is_null())
+            .lock_exclusive()
+            .one(&txn).await?;
+        if found.is_none() {
+            return Ok(React::NotFound);
+        }
+        if Entity::find_by_id((message_id, user_id, emoji.to_string())).one(&txn).await?.is_some() {
+            return Ok(React::AlreadyReacted);
+        }
+        let emojis = Entity::find()
+            .select_only()
+            .column(Column::Emoji)
+            .distinct()
+            .filter(Column::MessageId.eq(message_id))
+            .into_tuple::<String>()
+            .all(&txn).await?;
+        if !emojis.iter().any(|e| e == emoji) && emojis.len() as u64 >= max_distinct {
+            return Ok(React::LimitReached);
+        }
+        ActiveModel {
+            message_id: ActiveValue::Set(message_id),
+            user_id:    ActiveValue::Set(user_id),
+            emoji:      ActiveValue::Set(emoji.to_string()),
+            ..Default::default()
+        }.insert(&txn).await?;
+        let count = Entity::find()
+            .filter(Column::MessageId.eq(message_id))
+            .filter(Column::Emoji.eq(emoji))
+            .count(&txn).await?;
+        txn.commit().await?;
+        Ok(React::Added(count))
+    }
+
+    /// Takes the user's reaction back, returns how many are left with the emoji,
+    /// or `None` if they hadn't reacted with it.
+    pub async fn unreact(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<Option<u64>, Error> {
+        let txn = self.conn().begin().await?;
+        let res = Entity::delete_by_id((message_id, user_id, emoji.to_string())).exec(&txn).await?;
+        if res.rows_affected == 0 {
+            return Ok(None);
+        }
+        let count = Entity::find()
+            .filter(Column::MessageId.eq(message_id))
+            .filter(Column::Emoji.eq(emoji))
+            .count(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(count))
+    }
+
+    /// Reaction counts of each message, emojis in the order they were first used.
+    pub async fn select_counts(&self, message_ids: &[i32], user_id: i32) -> Result<HashMap<i32, Vec<Count>>, Error> {
+        let models = Entity::find()
+            .filter(Column::MessageId.is_in(message_ids.iter().copied()))
+            .order_by(Column::CreatedAt, Order::Asc)
+            .all(self.conn()).await?;
+        Ok(count(&models, user_id))
+    }
+}
+
+fn count(models: &[Model], user_id: i32) -> HashMap<i32, Vec<Count>> {
+    let mut counts = HashMap::<i32, Vec<Count>>::new();
+    for model in models {
+        let emojis = counts.entry(model.message_id).or_default();
+        let at = match emojis.iter().position(|c| c.emoji == model.emoji) {
+            Some(at) => at,
+            None => {
+                emojis.push(Count { emoji: model.emoji.clone(), count: 0, me: false });
+                emojis.len() - 1
+            }
+        };
+        emojis[at].count += 1;
+        emojis[at].me |= model.user_id == user_id;
+    }
+    counts
+}
+
+
+#[test]
+fn reaction_count_test() {
+    let reaction = |message_id, user_id, emoji: &str| Model {
+        message_id,
+        user_id,
+        emoji:      emoji.to_string(),
+        created_at: chrono::NaiveDateTime::default(),
+    };
+    let models = [
+        reaction(1, 10, "👍"),
+        reaction(1, 11, "🎉"),
+        reaction(1, 11, "👍"),
+        reaction(2, 12, "custom:42"),
+    ];
+    let counts = count(&models, 11);
+    assert_eq!(counts[&1], vec![
+        Count { emoji: "👍".to_string(), count: 2, me: true },
+        Count { emoji: "🎉".to_string(), count: 1, me: true },
+    ]);
+    assert_eq!(counts[&2], vec![Count { emoji: "custom:42".to_string(), count: 1, me: false }]);
+}
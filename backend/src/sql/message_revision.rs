@@ -0,0 +1,20 @@
+use crate::entities::prelude::MessageRevision;
+crate::database!(MessageRevision);
+
+use sea_orm::Order;
+
+/// `action` of a revision kept when a message was edited.
+pub const EDIT: &str = "edit";
+/// `action` of a revision kept when a message was deleted.
+pub const DELETE: &str = "delete";
+
+impl DB {
+    /// What a message said before each change, oldest first.
+    pub async fn select_message(&self, message_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::MessageId.eq(message_id)],
+            Some((Column::Id, Order::Asc)),
+        ).await?;
+        Ok(models)
+    }
+}
@@ -1,6 +1,21 @@
+pub(crate) mod category;
+pub(crate) mod category_identity;
+pub(crate) mod role;
 pub(crate) mod room;
 pub(crate) mod user;
 pub(crate) mod lone;
+pub(crate) mod lone_ban;
+pub(crate) mod lone_invite;
+pub(crate) mod lone_user;
+pub(crate) mod message;
+pub(crate) mod message_reaction;
+pub(crate) mod message_revision;
+pub(crate) mod recovery_code;
+pub(crate) mod refresh_token;
+pub(crate) mod room_identity;
+pub(crate) mod session;
+pub(crate) mod token_revocation;
+pub(crate) mod user_totp;
 
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
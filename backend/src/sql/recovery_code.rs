@@ -0,0 +1,41 @@
+use crate::entities::prelude::RecoveryCode;
+crate::database!(RecoveryCode);
+
+use sea_orm::{ActiveValue, QueryFilter, TransactionTrait};
+use sea_orm::sea_query::Expr;
+
+impl DB {
+    /// Drops every previous code of the user and stores the new digests.
+    pub async fn replace(&self, user_id: i32, code_hashes: Vec<String>) -> Result<(), Error> {
+        let txn = self.conn().begin().await?;
+        Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .exec(&txn).await?;
+        let models = code_hashes.into_iter().map(|code_hash| ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            code_hash:  ActiveValue::Set(code_hash),
+            ..Default::default()
+        });
+        Entity::insert_many(models).exec(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Spends a code. Returns `false` if it doesn't exist or was already used.
+    pub async fn consume(&self, user_id: i32, code_hash: &str) -> Result<bool, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::Used, Expr::value(true))
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::CodeHash.eq(code_hash))
+            .filter(Column::Used.eq(false))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected == 1)
+    }
+
+    pub async fn delete_user(&self, user_id: i32) -> Result<u64, Error> {
+        let res = Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::entities::prelude::RefreshToken;
+crate::database!(RefreshToken);
+
+use chrono::NaiveDateTime;
+use sea_orm::{ActiveValue, QueryFilter};
+use sea_orm::sea_query::Expr;
+
+impl DB {
+    pub async fn select_hash(&self, token_hash: &str) -> Result<Option<Model>, Error> {
+        let model
+            = self.select_one(vec![Column::TokenHash.eq(token_hash)]).await?;
+        Ok(model)
+    }
+
+    pub async fn issue(
+        &self, user_id: i32, family_id: &str, token_hash: String, expire_at: NaiveDateTime
+    ) -> Result<i32, Error> {
+        let model = ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            family_id:  ActiveValue::Set(family_id.to_string()),
+            token_hash: ActiveValue::Set(token_hash),
+            expire_at:  ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        Ok(self.insert(model).await?)
+    }
+
+    /// Marks a token as rotated. Returns `false` if it had already been used,
+    /// which means a concurrent request replayed the same token.
+    pub async fn mark_used(&self, id: i32) -> Result<bool, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::Used, Expr::value(true))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Used.eq(false))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected == 1)
+    }
+
+    pub async fn revoke_family(&self, family_id: &str) -> Result<u64, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::Revoked, Expr::value(true))
+            .filter(Column::FamilyId.eq(family_id))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected)
+    }
+
+    pub async fn revoke_user(&self, user_id: i32) -> Result<u64, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::Revoked, Expr::value(true))
+            .filter(Column::UserId.eq(user_id))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected)
+    }
+}
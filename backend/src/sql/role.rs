@@ -1,63 +1,119 @@
-use sea_orm::{
-    DbErr,
-    ActiveValue,
-    EntityTrait,
-    ActiveModelTrait,
-    DatabaseConnection,
-};
-
-use crate::entities::prelude::{LoneRoleInfo};
-use crate::entities::lone_role_info::{Column, ActiveModel, RolePrivilege, Model};
-use crate::sql::{BasicCRUD, DataBase, DataBaseConfig};
-
-pub use crate::entities::lone_role_info::LoneRoleTable as LoneRoleTable;
-use crate::id::RoleId;
-
-pub struct RoleDB {
-    conn: DatabaseConnection,
+use crate::entities::prelude::LoneRoleInfo;
+crate::database!(LoneRoleInfo);
+
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, Order, QueryFilter, QuerySelect, TransactionTrait};
+use sea_orm::sea_query::Expr;
+use crate::entities::{assoc_lone_user, lone_info};
+use crate::entities::lone_role_info::RolePrivilege;
+
+/// Moves the roles of a lone with `from <= position <= to` by `by`.
+async fn shift<C: ConnectionTrait>(conn: &C, lone_id: i32, from: i32, to: i32, by: i32) -> Result<(), Error> {
+    Entity::update_many()
+        .col_expr(Column::Position, Expr::col(Column::Position).add(by))
+        .filter(Column::LoneId.eq(lone_id))
+        .filter(Column::Position.between(from, to))
+        .exec(conn).await?;
+    Ok(())
 }
 
-impl DataBase for RoleDB {
-    type PrimaryKey = PrimaryKey;
-    type Table = LoneRoleTable;
-    type Column = Column;
-    type ActiveModel = ActiveModel;
-    type Model = Model;
-    type Entity = LoneRoleInfo;
+/// Serializes position changes within a lone.
+async fn lock_lone<C: ConnectionTrait>(conn: &C, lone_id: i32) -> Result<(), Error> {
+    lone_info::Entity::find_by_id(lone_id)
+        .lock_exclusive()
+        .one(conn).await?;
+    Ok(())
+}
 
-    fn with_conn(conn: DatabaseConnection) -> Self {
-        Self { conn }
-    }
-    fn conn(&self) -> &DatabaseConnection {
-        &self.conn
+impl DB {
+    /// Roles of a lone, highest ranked first.
+    pub async fn select_lone(&self, lone_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::LoneId.eq(lone_id)],
+            Some((Column::Position, Order::Desc)),
+        ).await?;
+        Ok(models)
     }
-}
 
-pub type PrimaryKey = i32;
+    /// Position of the highest role the user holds in the lone.
+    pub async fn highest_position(&self, lone_id: i32, user_id: i32) -> Result<Option<i32>, Error> {
+        let position = Entity::find()
+            .inner_join(assoc_lone_user::Entity)
+            .select_only()
+            .column_as(Column::Position.max(), "position")
+            .filter(assoc_lone_user::Column::LoneId.eq(lone_id))
+            .filter(assoc_lone_user::Column::UserId.eq(user_id))
+            .into_tuple::<Option<i32>>()
+            .one(self.conn()).await?;
+        Ok(position.flatten())
+    }
 
-impl RoleDB {
-    pub async fn from_cfg(config: &DataBaseConfig) -> Result<Self, anyhow::Error> {
-        let conn = config.to_conn().await?;
-        Ok(Self::with_conn(conn))
+    /// Creates a role ranked right above "@everyone", the roles above move up by one.
+    pub async fn create(&self, lone_id: i32, name: String, privilege: RolePrivilege) -> Result<Model, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        shift(&txn, lone_id, 1, i32::MAX - 1, 1).await?;
+        let model = ActiveModel {
+            name:       ActiveValue::Set(name),
+            lone_id:    ActiveValue::Set(lone_id),
+            privilege:  ActiveValue::Set(privilege.into()),
+            position:   ActiveValue::Set(1),
+            ..Default::default()
+        }.insert(&txn).await?;
+        txn.commit().await?;
+        Ok(model)
     }
 
     pub async fn update(
-        &self, role_id: RoleId,
-        privilege: Option<RolePrivilege>, name: Option<&str>
-    ) -> Result<(), DbErr> {
+        &self, role_id: i32,
+        privilege: Option<RolePrivilege>, name: Option<String>
+    ) -> Result<Model, Error> {
 
         let name =
-            name.map_or(ActiveValue::NotSet, |s| ActiveValue::Set(s.to_owned()));
+            name.map_or(ActiveValue::NotSet, ActiveValue::Set);
         let privilege =
             privilege.map_or(ActiveValue::NotSet, |s| ActiveValue::Set(s.into()));
 
         let model = ActiveModel {
-            id:         ActiveValue::Set(role_id.into()),
+            id:         ActiveValue::Set(role_id),
             name,
             privilege,
             lone_id:    ActiveValue::NotSet,
+            position:   ActiveValue::NotSet,
+        };
+        Ok(model.update(self.conn()).await?)
+    }
+
+    /// Moves a role to `position`, the roles in between close the gap.
+    pub async fn move_to(&self, role: &Model, position: i32) -> Result<Model, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, role.lone_id).await?;
+        // re-read, the rank may have changed before the lock
+        let role = Entity::find_by_id(role.id).one(&txn).await?
+            .ok_or_else(|| anyhow::anyhow!("role {} is gone", role.id))?;
+        if position > role.position {
+            shift(&txn, role.lone_id, role.position + 1, position, -1).await?;
+        } else if position < role.position {
+            shift(&txn, role.lone_id, position, role.position - 1, 1).await?;
+        }
+        let model = ActiveModel {
+            id:         ActiveValue::Set(role.id),
+            position:   ActiveValue::Set(position),
+            ..Default::default()
+        }.update(&txn).await?;
+        txn.commit().await?;
+        Ok(model)
+    }
+
+    /// Deletes a role, the members lose it and the roles above move down by one.
+    pub async fn remove(&self, role: &Model) -> Result<(), Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, role.lone_id).await?;
+        let Some(role) = Entity::find_by_id(role.id).one(&txn).await? else {
+            return Ok(());
         };
-        model.update(&self.conn).await?;
+        Entity::delete_by_id(role.id).exec(&txn).await?;
+        shift(&txn, role.lone_id, role.position + 1, i32::MAX, -1).await?;
+        txn.commit().await?;
         Ok(())
     }
 }
@@ -1,3 +1,148 @@
 use crate::entities::prelude::RoomInfo;
 crate::database!(RoomInfo);
 
+use std::collections::HashSet;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, Order, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+use crate::entities::lone_info;
+use crate::entities::room_info::RoomType;
+
+/// Serializes position changes within a lone.
+async fn lock_lone<C: ConnectionTrait>(conn: &C, lone_id: i32) -> Result<(), Error> {
+    lone_info::Entity::find_by_id(lone_id)
+        .lock_exclusive()
+        .one(conn).await?;
+    Ok(())
+}
+
+impl DB {
+    /// The lone a room belongs to, if the room exists.
+    pub async fn lone_of(&self, id: i32) -> Result<Option<i32>, Error> {
+        let lone_id = Entity::find_by_id(id)
+            .select_only()
+            .column(Column::LoneId)
+            .into_tuple::<i32>()
+            .one(self.conn()).await?;
+        Ok(lone_id)
+    }
+
+    /// Rooms of a lone in display order.
+    pub async fn select_lone(&self, lone_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::LoneId.eq(lone_id)],
+            Some((Column::Position, Order::Asc)),
+        ).await?;
+        Ok(models)
+    }
+
+    /// Creates a room at the end of the lone's rooms without a category.
+    pub async fn create(&self, lone_id: i32, name: String, room_type: RoomType) -> Result<Model, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let last = Entity::find()
+            .select_only()
+            .column_as(Column::Position.max(), "position")
+            .filter(Column::LoneId.eq(lone_id))
+            .filter(Column::CategoryId.is_null())
+            .into_tuple::<Option<i32>>()
+            .one(&txn).await?
+            .flatten();
+        let model = ActiveModel {
+            lone_id:    ActiveValue::Set(lone_id),
+            name:       ActiveValue::Set(name),
+            room_type:  ActiveValue::Set(room_type.into()),
+            position:   ActiveValue::Set(last.map_or(0, |last| last + 1)),
+            ..Default::default()
+        }.insert(&txn).await?;
+        txn.commit().await?;
+        Ok(model)
+    }
+
+    pub async fn rename(&self, id: i32, name: String) -> Result<Model, Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id),
+            name:       ActiveValue::Set(name),
+            ..Default::default()
+        };
+        Ok(model.update(self.conn()).await?)
+    }
+
+    /// Rooms of a lone under `category_id`, or under none, in display order.
+    pub async fn select_list(&self, lone_id: i32, category_id: Option<i32>) -> Result<Vec<Model>, Error> {
+        let models = Entity::find()
+            .filter(Column::LoneId.eq(lone_id))
+            .filter(in_list(category_id))
+            .order_by(Column::Position, Order::Asc)
+            .all(self.conn()).await?;
+        Ok(models)
+    }
+
+    /// Puts the rooms of a lone under `category_id`, or under none, in the order of `room_ids`.
+    /// `room_ids` must name each room of the list the caller can see once, rooms hidden from
+    /// them keep their slots. Positions count within that list only.
+    /// Returns the whole list in its new order, or `None` if `room_ids` doesn't match.
+    pub async fn reorder(
+        &self, lone_id: i32, category_id: Option<i32>, room_ids: &[i32], visible: &HashSet<i32>
+    ) -> Result<Option<Vec<Model>>, Error> {
+        let txn = self.conn().begin().await?;
+        lock_lone(&txn, lone_id).await?;
+        let current = Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .filter(Column::LoneId.eq(lone_id))
+            .filter(in_list(category_id))
+            .order_by(Column::Position, Order::Asc)
+            .into_tuple::<i32>()
+            .all(&txn).await?;
+        let Some(order) = interleave(&current, visible, room_ids) else {
+            return Ok(None);
+        };
+        for (position, id) in order.iter().enumerate() {
+            ActiveModel {
+                id:         ActiveValue::Set(*id),
+                position:   ActiveValue::Set(position as i32),
+                ..Default::default()
+            }.update(&txn).await?;
+        }
+        let models = Entity::find()
+            .filter(Column::Id.is_in(order))
+            .order_by(Column::Position, Order::Asc)
+            .all(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(models))
+    }
+}
+
+fn in_list(category_id: Option<i32>) -> sea_orm::sea_query::SimpleExpr {
+    match category_id {
+        Some(category_id) => Column::CategoryId.eq(category_id),
+        None => Column::CategoryId.is_null(),
+    }
+}
+
+/// The `current` list with its `visible` rooms put in the order of `room_ids`, the others
+/// staying where they are. `None` unless `room_ids` names every visible room once.
+fn interleave(current: &[i32], visible: &HashSet<i32>, room_ids: &[i32]) -> Option<Vec<i32>> {
+    let shown = current.iter().copied().filter(|id| visible.contains(id)).collect::<HashSet<_>>();
+    let wanted = room_ids.iter().copied().collect::<HashSet<_>>();
+    if wanted.len() != room_ids.len() || wanted != shown {
+        return None;
+    }
+    let mut room_ids = room_ids.iter();
+    current.iter()
+        .map(|id| if shown.contains(id) { room_ids.next().copied() } else { Some(*id) })
+        .collect()
+}
+
+
+#[test]
+fn room_interleave_test() {
+    let visible = HashSet::from([1, 3, 4]);
+    // 2 and 5 are hidden and keep their slots
+    assert_eq!(interleave(&[1, 2, 3, 4, 5], &visible, &[4, 1, 3]), Some(vec![4, 2, 1, 3, 5]));
+    assert_eq!(interleave(&[1, 2, 3, 4, 5], &visible, &[4, 1]), None);
+    assert_eq!(interleave(&[1, 2, 3, 4, 5], &visible, &[4, 1, 3, 3]), None);
+    // naming a hidden room looks the same as naming a missing one
+    assert_eq!(interleave(&[1, 2, 3, 4, 5], &visible, &[4, 1, 3, 2]), None);
+    assert_eq!(interleave(&[1, 2, 3, 4, 5], &visible, &[4, 1, 3, 6]), None);
+    assert_eq!(interleave(&[2], &visible, &[]), Some(vec![2]));
+}
@@ -0,0 +1,28 @@
+use crate::entities::prelude::RoomIdentityInfo;
+crate::database!(RoomIdentityInfo);
+
+use sea_orm::{Condition, QueryFilter, QuerySelect};
+use crate::entities::assoc_room_user;
+use crate::sql::lone::EVERYONE_ROLE;
+
+impl DB {
+    /// Identities of the room that apply to the user: the "@everyone" ones and those they hold.
+    pub async fn select_overrides(&self, room_id: i32, user_id: i32) -> Result<Vec<Model>, Error> {
+        let iden_ids = assoc_room_user::Entity::find()
+            .select_only()
+            .column(assoc_room_user::Column::IdenId)
+            .filter(assoc_room_user::Column::RoomId.eq(room_id))
+            .filter(assoc_room_user::Column::UserId.eq(user_id))
+            .into_tuple::<i32>()
+            .all(self.conn()).await?;
+        let models = Entity::find()
+            .filter(Column::RoomId.eq(room_id))
+            .filter(
+                Condition::any()
+                    .add(Column::Name.eq(EVERYONE_ROLE))
+                    .add(Column::Id.is_in(iden_ids))
+            )
+            .all(self.conn()).await?;
+        Ok(models)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::entities::prelude::UserSession;
+crate::database!(UserSession);
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue, Order, QueryFilter};
+use sea_orm::sea_query::Expr;
+
+impl DB {
+    pub async fn create(
+        &self, id: &str, user_id: i32, ip: String, user_agent: Option<String>
+    ) -> Result<(), Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id.to_string()),
+            user_id:    ActiveValue::Set(user_id),
+            ip:         ActiveValue::Set(ip),
+            user_agent: ActiveValue::Set(user_agent),
+            ..Default::default()
+        };
+        self.insert(model).await?;
+        Ok(())
+    }
+
+    /// Live sessions of a user, most recently used first.
+    pub async fn select_user(&self, user_id: i32) -> Result<Vec<Model>, Error> {
+        let models = self.select(
+            vec![Column::UserId.eq(user_id), Column::Revoked.eq(false)],
+            Some((Column::LastSeen, Order::Desc)),
+        ).await?;
+        Ok(models)
+    }
+
+    pub async fn touch(&self, id: &str) -> Result<(), Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id.to_string()),
+            last_seen:  ActiveValue::Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        model.update(self.conn()).await?;
+        Ok(())
+    }
+
+    pub async fn revoke(&self, id: &str) -> Result<(), Error> {
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id.to_string()),
+            revoked:    ActiveValue::Set(true),
+            ..Default::default()
+        };
+        model.update(self.conn()).await?;
+        Ok(())
+    }
+
+    pub async fn revoke_user(&self, user_id: i32) -> Result<u64, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::Revoked, Expr::value(true))
+            .filter(Column::UserId.eq(user_id))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected)
+    }
+}
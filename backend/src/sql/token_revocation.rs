@@ -0,0 +1,64 @@
+use crate::entities::prelude::TokenRevocation;
+crate::database!(TokenRevocation);
+
+use chrono::NaiveDateTime;
+use sea_orm::{ActiveValue, QueryFilter};
+use sea_orm::sea_query::OnConflict;
+
+impl DB {
+    pub async fn select_active(&self, now: NaiveDateTime) -> Result<Vec<Model>, Error> {
+        let models
+            = self.select(vec![Column::ExpireAt.gt(now)], None).await?;
+        Ok(models)
+    }
+
+    pub async fn revoke_jti(
+        &self, user_id: i32, jti: &str, expire_at: NaiveDateTime
+    ) -> Result<(), Error> {
+        let model = ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            jti:        ActiveValue::Set(Some(jti.to_string())),
+            expire_at:  ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        Entity::insert(model)
+            .on_conflict(OnConflict::column(Column::Jti).do_nothing().to_owned())
+            .do_nothing()
+            .exec(self.conn()).await?;
+        Ok(())
+    }
+
+    pub async fn revoke_session(
+        &self, user_id: i32, session_id: &str, expire_at: NaiveDateTime
+    ) -> Result<(), Error> {
+        let model = ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            session_id: ActiveValue::Set(Some(session_id.to_string())),
+            expire_at:  ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        self.insert(model).await?;
+        Ok(())
+    }
+
+    pub async fn revoke_user(
+        &self, user_id: i32, revoked_at: NaiveDateTime, expire_at: NaiveDateTime
+    ) -> Result<(), Error> {
+        let model = ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            jti:        ActiveValue::Set(None),
+            revoked_at: ActiveValue::Set(revoked_at),
+            expire_at:  ActiveValue::Set(expire_at),
+            ..Default::default()
+        };
+        self.insert(model).await?;
+        Ok(())
+    }
+
+    pub async fn delete_expired(&self, now: NaiveDateTime) -> Result<u64, Error> {
+        let res = Entity::delete_many()
+            .filter(Column::ExpireAt.lte(now))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected)
+    }
+}
@@ -7,6 +7,30 @@ impl DB {
             = self.select_one(vec![Column::Email.eq(email)]).await?;
         Ok(model)
     }
+
+    pub async fn update_password(&self, id: i32, password: String) -> Result<(), Error> {
+        use sea_orm::{ActiveModelTrait, ActiveValue};
+        let model = ActiveModel {
+            id:         ActiveValue::Set(id),
+            password:   ActiveValue::Set(password),
+            ..Default::default()
+        };
+        model.update(self.conn()).await?;
+        Ok(())
+    }
+
+    /// Swaps the password only if it is still `current`.
+    /// Returns `false` when another request changed it first.
+    pub async fn replace_password(&self, id: i32, current: &str, password: String) -> Result<bool, Error> {
+        use sea_orm::QueryFilter;
+        use sea_orm::sea_query::Expr;
+        let res = Entity::update_many()
+            .col_expr(Column::Password, Expr::value(password))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Password.eq(current))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected == 1)
+    }
 }
 
 
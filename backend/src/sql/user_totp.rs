@@ -0,0 +1,55 @@
+use crate::entities::prelude::UserTotp;
+crate::database!(UserTotp);
+
+use sea_orm::{ActiveValue, QueryFilter};
+use sea_orm::sea_query::{Expr, OnConflict};
+
+impl DB {
+    pub async fn select_user(&self, user_id: i32) -> Result<Option<Model>, Error> {
+        let model = self.select_pk(user_id).await?;
+        Ok(model)
+    }
+
+    /// Stores a secret waiting for confirmation, replacing any earlier unconfirmed one.
+    pub async fn enroll(&self, user_id: i32, secret: String) -> Result<(), Error> {
+        let model = ActiveModel {
+            user_id:    ActiveValue::Set(user_id),
+            secret:     ActiveValue::Set(secret),
+            enabled:    ActiveValue::Set(false),
+            last_step:  ActiveValue::Set(0),
+            ..Default::default()
+        };
+        Entity::insert(model)
+            .on_conflict(
+                OnConflict::column(Column::UserId)
+                    .update_columns([Column::Secret, Column::Enabled, Column::LastStep])
+                    .to_owned()
+            )
+            .exec(self.conn()).await?;
+        Ok(())
+    }
+
+    pub async fn enable(&self, user_id: i32) -> Result<(), Error> {
+        Entity::update_many()
+            .col_expr(Column::Enabled, Expr::value(true))
+            .filter(Column::UserId.eq(user_id))
+            .exec(self.conn()).await?;
+        Ok(())
+    }
+
+    /// Records the step a code was accepted for. Returns `false` if that step or a later
+    /// one was already used, i.e. the code is being replayed.
+    pub async fn advance_step(&self, user_id: i32, step: i64) -> Result<bool, Error> {
+        let res = Entity::update_many()
+            .col_expr(Column::LastStep, Expr::value(step))
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::LastStep.lt(step))
+            .exec(self.conn()).await?;
+        Ok(res.rows_affected == 1)
+    }
+
+    pub async fn delete_user(&self, user_id: i32) -> Result<(), Error> {
+        self.delete_pk(user_id).await?;
+        Ok(())
+    }
+}
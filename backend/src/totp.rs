@@ -0,0 +1,130 @@
+use data_encoding::BASE32_NOPAD;
+use rand::{Rng, RngCore};
+use ring::digest::{digest, SHA256};
+use ring::hmac;
+
+use crate::password::constant_time_eq;
+
+// RFC 6238 defaults, the only parameters most authenticator apps understand.
+const TOTP_STEP: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const TOTP_SECRET_BYTES: usize = 20;
+/// Steps accepted on either side of the current one to absorb clock drift.
+const TOTP_SKEW: u64 = 1;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_LEN: usize = 10;
+
+pub const ISSUER: &str = "ChatAlone";
+
+/// A fresh base32 encoded shared secret.
+pub fn gen_secret() -> String {
+    let mut bytes = [0u8; TOTP_SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn percent_encode(s: &str) -> String {
+    s.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+/// Key URI understood by authenticator apps, usually shown as a QR code.
+/// Issuer and account are encoded in full, so neither can add a label separator or parameter.
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP}",
+        issuer = percent_encode(ISSUER),
+        account = percent_encode(account),
+    )
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    // SHA1 is what RFC 6238 and every authenticator app use.
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = tag.as_ref();
+
+    // RFC 4226 dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    bin % 10u32.pow(TOTP_DIGITS)
+}
+
+pub fn step_at(unix_s: u64) -> u64 {
+    unix_s / TOTP_STEP
+}
+
+/// Checks `code` against the steps around `unix_s`, skipping any step not after `last_step`
+/// so a code can't be replayed. Returns the step that matched.
+pub fn verify(secret: &str, code: &str, unix_s: u64, last_step: u64) -> Option<u64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let now = step_at(unix_s);
+    (now.saturating_sub(TOTP_SKEW)..=now + TOTP_SKEW)
+        .filter(|step| *step > last_step)
+        .find(|step| {
+            let expected = format!("{:0width$}", hotp(&key, *step), width = TOTP_DIGITS as usize);
+            constant_time_eq(expected.as_bytes(), code.as_bytes())
+        })
+}
+
+/// One-time codes handed out when 2FA is confirmed, only their digests are kept.
+pub fn gen_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT).map(|_| {
+        let code: String = (0..RECOVERY_CODE_LEN)
+            .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+            .collect();
+        format!("{}-{}", &code[..RECOVERY_CODE_LEN / 2], &code[RECOVERY_CODE_LEN / 2..])
+    }).collect()
+}
+
+/// Digest of a recovery code, ignoring case and the separator.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(digest(&SHA256, normalized.as_bytes()))
+}
+
+
+#[test]
+fn totp_test() {
+    // RFC 6238 appendix B, SHA1 with the last 6 of its 8 digits
+    let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+    assert_eq!(verify(&secret, "287082", 59, 0), Some(1));
+    assert_eq!(verify(&secret, "081804", 1111111109, 0), Some(step_at(1111111109)));
+    assert_eq!(verify(&secret, "050471", 1111111111, 0), Some(step_at(1111111111)));
+
+    // neighbouring steps are accepted, replays are not
+    assert_eq!(verify(&secret, "287082", 59 + 30, 0), Some(1));
+    assert_eq!(verify(&secret, "287082", 59, 1), None);
+    assert_eq!(verify(&secret, "287082", 59 + 90, 0), None);
+    assert_eq!(verify(&secret, "28708", 59, 0), None);
+
+    let secret = gen_secret();
+    assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), TOTP_SECRET_BYTES);
+    assert!(otpauth_uri(&secret, "a@b.com").starts_with("otpauth://totp/ChatAlone:a%40b.com?secret="));
+    assert!(otpauth_uri(&secret, "a:b&c=d é@b.com")
+        .starts_with("otpauth://totp/ChatAlone:a%3Ab%26c%3Dd%20%C3%A9%40b.com?secret="));
+
+    let codes = gen_recovery_codes();
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    assert_eq!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[0].to_uppercase().replace('-', "")));
+    assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+}